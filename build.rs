@@ -5,15 +5,319 @@ use std::{
     path::Path,
 };
 
+extern crate alloc;
+
+#[path = "src/frames.rs"]
+mod frames;
+#[path = "src/gamma.rs"]
+mod gamma;
+
+const TRANSFORMS: &[&str] = &[
+    "None",
+    "FlipH",
+    "FlipV",
+    "Rotate180",
+    "Rotate90CW",
+    "Rotate90CCW",
+];
+
+const RENDER_MODES: &[&str] = &["Threshold", "Tint", "Sepia", "Palette", "Edges"];
+
+const SCALE_MODES: &[&str] = &["None", "Integer", "CenterCrop"];
+
+const IDLE_STRATEGIES: &[&str] = &["Spin", "Halt"];
+
+const END_BEHAVIORS: &[&str] = &["HoldLastFrame", "Clear", "Loop", "Exit"];
+
 fn main() {
     println!("cargo:rerun-if-changed=ascii.txt");
     println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/frames.rs");
+    println!("cargo:rerun-if-changed=src/gamma.rs");
 
     let out_dir = env::var("OUT_DIR").unwrap();
     let dest_path = Path::new(&out_dir).join("ascii.rs");
     let mut f = File::create(dest_path).unwrap();
     let ascii_file = read_to_string("ascii.txt").expect("failed to read computed ASCII file");
-    let frames = ascii_file.split("SPLIT").collect::<Vec<&str>>();
+
+    // NOTE: A small frame cap keeps the binary tiny and the build quick while iterating
+    let max_frames = match config_number::<usize>("BAD_APPLE_MAX_FRAMES") {
+        Some(0) => {
+            println!("cargo:warning=BAD_APPLE_MAX_FRAMES must not be zero, using default");
+            None
+        }
+        max_frames => max_frames,
+    };
+    let mut frames = frames::split(&ascii_file, max_frames);
+
+    // NOTE: Frames past the cap are never looked at, so only a cut short frame which would
+    // actually be embedded fails a strict build
+    let strict = config_var("BAD_APPLE_STRICT_FRAMES").is_some_and(|value| parse_flag(&value));
+    match frames::drop_truncated(&mut frames, strict) {
+        Ok(true) => println!("cargo:warning=dropping truncated last frame from ascii.txt"),
+        Ok(false) => {}
+        Err(error) => panic!("{error}"),
+    }
 
     let _ = f.write_all(format!("static ASCII_FRAMES: &[&str] = &{:?};", frames).as_bytes());
+
+    let mut f = File::create(Path::new(&out_dir).join("config.rs")).unwrap();
+    let _ = f.write_all(
+        format!(
+            "#[allow(clippy::needless_update)]\npub const CONFIG: PlaybackConfig = PlaybackConfig {{ {} ..PlaybackConfig::DEFAULT }};",
+            playback_config()
+        )
+        .as_bytes(),
+    );
+}
+
+/// Builds the field initializers for `PlaybackConfig` from the environment,
+/// leaving out anything unset or invalid so that it falls back to the default.
+fn playback_config() -> String {
+    let mut fields = String::new();
+
+    if let Some(transform) = config_variant("BAD_APPLE_TRANSFORM", TRANSFORMS) {
+        fields += &format!("transform: Transform::{transform}, ");
+    }
+
+    if let Some(scale) = config_variant("BAD_APPLE_SCALE", SCALE_MODES) {
+        fields += &format!("scale: ScaleMode::{scale}, ");
+    }
+
+    if let Some(crop) = config_var("BAD_APPLE_CROP") {
+        match crop
+            .split(',')
+            .map(|value| value.parse::<u16>())
+            .collect::<Result<Vec<_>, _>>()
+            .as_deref()
+        {
+            Ok(&[x, y, width, height]) if width != 0 && height != 0 => {
+                fields += &format!("crop: Some(({x}, {y}, {width}, {height})), ")
+            }
+            _ => println!("cargo:warning=invalid BAD_APPLE_CROP `{crop}`, using default"),
+        }
+    }
+
+    if let Some(aspect) = config_var("BAD_APPLE_PIXEL_ASPECT") {
+        match aspect
+            .split_once(':')
+            .map(|(x, y)| (x.parse::<u16>(), y.parse::<u16>()))
+        {
+            Some((Ok(x), Ok(y))) if x != 0 && y != 0 => {
+                fields += &format!("pixel_aspect: ({x}, {y}), ")
+            }
+            _ => println!("cargo:warning=invalid BAD_APPLE_PIXEL_ASPECT `{aspect}`, using default"),
+        }
+    }
+
+    if let Some(resolution) = config_var("BAD_APPLE_RESOLUTION") {
+        match resolution
+            .split_once('x')
+            .map(|(width, height)| (width.parse::<u16>(), height.parse::<u16>()))
+        {
+            Some((Ok(width), Ok(height))) if width != 0 && height != 0 => {
+                fields += &format!("resolution: Some(({width}, {height})), ")
+            }
+            _ => {
+                println!("cargo:warning=invalid BAD_APPLE_RESOLUTION `{resolution}`, using default")
+            }
+        }
+    }
+
+    // NOTE: `BAD_APPLE_LOOP` predates the other end behaviors, so it is kept as a shorthand
+    // for `BAD_APPLE_END=Loop`, which takes precedence when both are set
+    let looping = config_var("BAD_APPLE_LOOP")
+        .is_some_and(|looping| parse_flag(&looping))
+        .then(|| String::from("Loop"));
+    if let Some(end) = config_variant("BAD_APPLE_END", END_BEHAVIORS).or(looping) {
+        fields += &format!("end: EndBehavior::{end}, ");
+    }
+
+    match config_number::<u32>("BAD_APPLE_FPS") {
+        Some(0) => println!("cargo:warning=BAD_APPLE_FPS must not be zero, using default"),
+        Some(fps) => fields += &format!("fps: {fps}, "),
+        None => {}
+    }
+
+    if let Some(drop_frames) = config_var("BAD_APPLE_DROP_FRAMES") {
+        fields += &format!("drop_frames: {}, ", parse_flag(&drop_frames));
+    }
+
+    if let Some(interpolate) = config_var("BAD_APPLE_INTERPOLATE") {
+        fields += &format!("interpolate: {}, ", parse_flag(&interpolate));
+    }
+
+    match config_number::<u32>("BAD_APPLE_CALIBRATION_MS") {
+        Some(0) => {
+            println!("cargo:warning=BAD_APPLE_CALIBRATION_MS must not be zero, using default")
+        }
+        Some(calibration_ms) => fields += &format!("calibration_ms: {calibration_ms}, "),
+        None => {}
+    }
+
+    if let Some(idle) = config_variant("BAD_APPLE_IDLE", IDLE_STRATEGIES) {
+        fields += &format!("idle: IdleStrategy::{idle}, ");
+    }
+
+    if let Some(step_mode) = config_var("BAD_APPLE_STEP_MODE") {
+        fields += &format!("step_mode: {}, ", parse_flag(&step_mode));
+    }
+
+    if let Some(start_frame) = config_number::<usize>("BAD_APPLE_START_FRAME") {
+        fields += &format!("start_frame: {start_frame}, ");
+    }
+
+    if let Some(reverse) = config_var("BAD_APPLE_REVERSE") {
+        fields += &format!("reverse: {}, ", parse_flag(&reverse));
+    }
+
+    if let Some(dump_frame) = config_number::<usize>("BAD_APPLE_DUMP_FRAME") {
+        fields += &format!("dump_frame: Some({dump_frame}), ");
+    }
+
+    if let Some(checksum) = config_var("BAD_APPLE_CHECKSUM") {
+        fields += &format!("checksum: {}, ", parse_flag(&checksum));
+    }
+
+    if let Some(show_frame_index) = config_var("BAD_APPLE_SHOW_FRAME_INDEX") {
+        fields += &format!("show_frame_index: {}, ", parse_flag(&show_frame_index));
+    }
+
+    if let Some(poster_frame) = config_number::<usize>("BAD_APPLE_POSTER_FRAME") {
+        fields += &format!("poster_frame: Some({poster_frame}), ");
+    }
+
+    if let Some(poster_hold_ms) = config_number::<u32>("BAD_APPLE_POSTER_HOLD_MS") {
+        fields += &format!("poster_hold_ms: {poster_hold_ms}, ");
+    }
+
+    // NOTE: The lookup table is built here, since `core` has no `powf` to build it with at runtime
+    match config_number::<f64>("BAD_APPLE_GAMMA") {
+        Some(gamma) if gamma.is_finite() && gamma > 0.0 => {
+            fields += &format!("gamma: &{:?}, ", gamma::lut(gamma));
+        }
+        Some(gamma) => {
+            println!("cargo:warning=BAD_APPLE_GAMMA must be positive, not `{gamma}`, using default")
+        }
+        None => {}
+    }
+
+    if let Some(mode) = config_variant("BAD_APPLE_MODE", RENDER_MODES) {
+        fields += &format!("mode: RenderMode::{mode}, ");
+    }
+
+    if let Some(tint) = config_color("BAD_APPLE_TINT") {
+        fields += &format!("tint: {tint}, ");
+    }
+
+    if let Some(palette) = config_colors("BAD_APPLE_PALETTE") {
+        fields += &format!("fixed_palette: {palette}, ");
+    }
+
+    if let Some(edge_threshold) = config_number::<u8>("BAD_APPLE_EDGE_THRESHOLD") {
+        fields += &format!("edge_threshold: {edge_threshold}, ");
+    }
+
+    if let Some(low_color) = config_color("BAD_APPLE_LOW_COLOR") {
+        fields += &format!("low_color: {low_color}, ");
+    }
+
+    if let Some(high_color) = config_color("BAD_APPLE_HIGH_COLOR") {
+        fields += &format!("high_color: {high_color}, ");
+    }
+
+    if let Some(threshold) = config_number::<u8>("BAD_APPLE_THRESHOLD") {
+        fields += &format!("threshold: {threshold}, ");
+    }
+
+    if let Some(auto_threshold) = config_var("BAD_APPLE_AUTO_THRESHOLD") {
+        fields += &format!("auto_threshold: {}, ", parse_flag(&auto_threshold));
+    }
+
+    if let Some(invert) = config_var("BAD_APPLE_INVERT") {
+        fields += &format!("invert: {}, ", parse_flag(&invert));
+    }
+
+    if let Some(background) = config_color("BAD_APPLE_BACKGROUND") {
+        fields += &format!("background: Some({background}), ");
+    }
+
+    if let Some(hide_cursor) = config_var("BAD_APPLE_HIDE_CURSOR") {
+        fields += &format!("hide_cursor: {}, ", parse_flag(&hide_cursor));
+    }
+
+    fields
+}
+
+fn config_var(name: &str) -> Option<String> {
+    println!("cargo:rerun-if-env-changed={name}");
+    env::var(name).ok()
+}
+
+fn config_variant(name: &str, variants: &[&str]) -> Option<String> {
+    let value = config_var(name)?;
+    if !variants.contains(&value.as_str()) {
+        println!("cargo:warning=unknown {name} `{value}`, using default");
+        return None;
+    }
+
+    Some(value)
+}
+
+fn config_number<T: std::str::FromStr>(name: &str) -> Option<T> {
+    let value = config_var(name)?;
+    let parsed = value.parse().ok();
+    if parsed.is_none() {
+        println!("cargo:warning=invalid {name} `{value}`, using default");
+    }
+
+    parsed
+}
+
+/// Reads an `RRGGBB` hex color, as a `BltPixel` constructor.
+fn config_color(name: &str) -> Option<String> {
+    let value = config_var(name)?;
+    let color = parse_color(&value);
+    if color.is_none() {
+        println!("cargo:warning=invalid {name} `{value}`, expected RRGGBB, using default");
+    }
+
+    color
+}
+
+/// Reads a comma separated list of `RRGGBB` hex colors, as a `BltPixel` slice.
+fn config_colors(name: &str) -> Option<String> {
+    let value = config_var(name)?;
+    let colors = value
+        .split(',')
+        .filter(|color| !color.trim().is_empty())
+        .map(|color| parse_color(color.trim()))
+        .collect::<Option<Vec<_>>>();
+
+    let Some(colors) = colors else {
+        println!(
+            "cargo:warning=invalid {name} `{value}`, expected RRGGBB,RRGGBB,..., using default"
+        );
+        return None;
+    };
+
+    Some(format!("&[{}]", colors.join(", ")))
+}
+
+fn parse_color(value: &str) -> Option<String> {
+    let hex = value.trim_start_matches('#');
+    let rgb = Some(hex)
+        .filter(|hex| hex.len() == 6 && hex.chars().all(|char| char.is_ascii_hexdigit()))
+        .and_then(|hex| u32::from_str_radix(hex, 16).ok())?;
+
+    Some(format!(
+        "BltPixel::new({}, {}, {})",
+        rgb >> 16,
+        (rgb >> 8) & 0xff,
+        rgb & 0xff
+    ))
+}
+
+fn parse_flag(value: &str) -> bool {
+    matches!(value, "1" | "true" | "yes" | "on")
 }
@@ -0,0 +1,177 @@
+use core::arch::x86_64::_rdtsc;
+
+use uefi::table::boot::{BootServices, EventType, TimerTrigger, Tpl};
+
+/// Monotonic microsecond clock, backed by the TSC and calibrated against the
+/// firmware's stall. This assumes an invariant TSC, which any CPU new enough to
+/// boot UEFI has.
+pub struct Clock {
+    start: u64,
+    ticks_per_us: u64,
+}
+
+impl Clock {
+    /// Calibrates the clock by counting TSC ticks over a `window_us` long stall,
+    /// where a longer window evens out noise from virtualization.
+    pub fn calibrate(boot_services: &BootServices, window_us: u64) -> Self {
+        let window_us = window_us.max(1);
+
+        // SAFETY: `rdtsc` is available on every x86_64 CPU
+        let start = unsafe { _rdtsc() };
+        boot_services.stall(window_us as usize);
+        let end = unsafe { _rdtsc() };
+
+        Self {
+            start: end,
+            ticks_per_us: ((end - start) / window_us).max(1),
+        }
+    }
+
+    /// Calibrated TSC frequency, in ticks per microsecond.
+    #[cfg(feature = "selftest")]
+    pub fn ticks_per_us(&self) -> u64 {
+        self.ticks_per_us
+    }
+
+    /// Microseconds elapsed since calibration.
+    pub fn now_us(&self) -> u64 {
+        // SAFETY: `rdtsc` is available on every x86_64 CPU
+        (unsafe { _rdtsc() } - self.start) / self.ticks_per_us
+    }
+}
+
+/// Paces frames against an absolute schedule rather than a fixed delay after
+/// each one, so that time spent rendering and waiting can't build up into drift
+/// over a long playback.
+pub struct FrameClock {
+    interval_us: u64,
+    epoch_us: u64,
+}
+
+impl FrameClock {
+    /// Starts a schedule of frames `interval_us` apart, with the first one due at `now_us`.
+    pub fn new(interval_us: u64, now_us: u64) -> Self {
+        Self {
+            interval_us,
+            epoch_us: now_us,
+        }
+    }
+
+    /// Microseconds to wait until the `n`th frame is due, or `None` if it is already late.
+    pub fn next_delay(&self, n: usize, now_us: u64) -> Option<u64> {
+        self.due_us(n).checked_sub(now_us)
+    }
+
+    /// When the `n`th frame is due.
+    pub fn due_us(&self, n: usize) -> u64 {
+        self.epoch_us + n as u64 * self.interval_us
+    }
+
+    /// When a frame halfway between the `n`th and the one after it is due.
+    pub fn halfway_us(&self, n: usize) -> u64 {
+        self.due_us(n) + self.interval_us / 2
+    }
+
+    /// Whether the `n`th frame is so late that the one after it is due already.
+    pub fn is_behind(&self, n: usize, now_us: u64) -> bool {
+        self.next_delay(n + 1, now_us).is_none()
+    }
+
+    /// Pushes the whole schedule back by `us`, e.g. to resume after a pause.
+    pub fn delay_by(&mut self, us: u64) {
+        self.epoch_us += us;
+    }
+}
+
+/// How the CPU passes the time while waiting.
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum IdleStrategy {
+    /// Busy-wait through the firmware's stall, which is precise but pegs a core.
+    Spin,
+    /// Wait on a firmware timer event, which lets the firmware halt the CPU until
+    /// the next interrupt. This relies on the firmware's timer interrupt, which only
+    /// keeps running while boot services are active.
+    Halt,
+}
+
+/// Waits for `us` microseconds using the given `strategy`.
+pub fn wait_us(boot_services: &BootServices, us: u64, strategy: IdleStrategy) {
+    match strategy {
+        IdleStrategy::Spin => boot_services.stall(us as usize),
+        IdleStrategy::Halt => {
+            // SAFETY: The event has no notify function, so there is no callback which could
+            // outlive anything
+            let event = unsafe {
+                boot_services.create_event(EventType::TIMER, Tpl::APPLICATION, None, None)
+            }
+            .expect("failed to create timer event");
+
+            // NOTE: Timer events count in 100ns units
+            boot_services
+                .set_timer(&event, TimerTrigger::Relative(us * 10))
+                .expect("failed to set timer");
+
+            // SAFETY: The clone is only used for this wait, the event is closed right after it
+            let mut events = [unsafe { event.unsafe_clone() }];
+            boot_services
+                .wait_for_event(&mut events)
+                .expect("failed to wait for timer");
+
+            boot_services
+                .close_event(event)
+                .expect("failed to close timer event");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_clock_keeps_schedule() {
+        let frame_clock = FrameClock::new(100_000, 1_000);
+
+        assert_eq!(frame_clock.due_us(0), 1_000);
+        assert_eq!(frame_clock.due_us(3), 301_000);
+        assert_eq!(frame_clock.halfway_us(3), 351_000);
+        assert_eq!(frame_clock.next_delay(1, 51_000), Some(50_000));
+        assert_eq!(frame_clock.next_delay(1, 101_000), Some(0));
+    }
+
+    #[test]
+    fn frame_clock_catches_up_after_slow_frames() {
+        let frame_clock = FrameClock::new(100_000, 0);
+
+        // NOTE: Frame 1 took until 250ms, so frame 2 is late and presented straight away
+        assert_eq!(frame_clock.next_delay(2, 250_000), None);
+        assert!(!frame_clock.is_behind(2, 250_000));
+
+        // NOTE: Frame 3 still keeps to the original schedule, which is how lost time is made up
+        assert_eq!(frame_clock.next_delay(3, 260_000), Some(40_000));
+    }
+
+    #[test]
+    fn frame_clock_is_behind() {
+        let frame_clock = FrameClock::new(100_000, 0);
+
+        assert!(!frame_clock.is_behind(1, 150_000));
+        assert!(!frame_clock.is_behind(1, 200_000));
+        assert!(frame_clock.is_behind(1, 200_001));
+        assert!(frame_clock.is_behind(1, 450_000));
+    }
+
+    #[test]
+    fn frame_clock_delay_by_realigns_after_pause() {
+        let mut frame_clock = FrameClock::new(100_000, 0);
+
+        // NOTE: Paused on frame 4 for 2s, after which frame 5 is due a whole interval later
+        // rather than immediately
+        frame_clock.delay_by(2_000_000);
+
+        assert_eq!(frame_clock.due_us(5), 2_500_000);
+        assert_eq!(frame_clock.next_delay(5, 2_400_000), Some(100_000));
+        assert!(!frame_clock.is_behind(5, 2_400_000));
+    }
+}
@@ -0,0 +1,306 @@
+use uefi::proto::console::gop::BltPixel;
+
+use crate::{
+    clock::IdleStrategy,
+    render::{RenderMode, ScaleMode, Transform, IDENTITY_GAMMA},
+};
+
+include!(concat!(env!("OUT_DIR"), "/config.rs"));
+
+/// What playback does once it runs out of frames.
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum EndBehavior {
+    /// Keep the last frame on screen until a key is pressed, then exit.
+    HoldLastFrame,
+    /// Clear the display to the background color, then exit.
+    Clear,
+    /// Start over from the start frame, forever.
+    Loop,
+    /// Leave the last frame up for a second, then exit.
+    Exit,
+}
+
+/// What is done once playback runs out of frames, in the order the fields are in.
+#[derive(Clone, Copy)]
+pub struct EndActions {
+    /// Whether playback starts over instead of ending.
+    pub restart: bool,
+    /// Whether a key press is waited for.
+    pub wait_for_key: bool,
+    /// Whether the display is cleared to the background color.
+    pub clear: bool,
+    /// How long the last frame stays up before exiting, in microseconds.
+    pub linger_us: u64,
+}
+
+impl EndBehavior {
+    /// Breaks the behavior down into the steps the players take.
+    pub const fn actions(self) -> EndActions {
+        let none = EndActions {
+            restart: false,
+            wait_for_key: false,
+            clear: false,
+            linger_us: 0,
+        };
+
+        match self {
+            // NOTE: Waiting on the key event lets the firmware halt the CPU, and nothing is drawn
+            // over the last frame in the meantime
+            EndBehavior::HoldLastFrame => EndActions {
+                wait_for_key: true,
+                ..none
+            },
+            EndBehavior::Clear => EndActions {
+                clear: true,
+                ..none
+            },
+            EndBehavior::Loop => EndActions {
+                restart: true,
+                ..none
+            },
+            EndBehavior::Exit => EndActions {
+                linger_us: 1_000_000,
+                ..none
+            },
+        }
+    }
+}
+
+/// Non-code playback settings, baked in at build time by `build.rs` from the
+/// `BAD_APPLE_*` environment variables. Anything left unset keeps its default.
+#[derive(Clone, Copy)]
+pub struct PlaybackConfig {
+    /// Orientation applied to each frame before it is drawn.
+    pub transform: Transform,
+    /// How each frame is fitted onto the display.
+    pub scale: ScaleMode,
+    /// Window of each frame, as `(x, y, width, height)` after the transform, which
+    /// is drawn and scaled instead of the whole frame.
+    pub crop: Option<(usize, usize, usize, usize)>,
+    /// Width to height ratio of a source pixel, which frames are stretched by to
+    /// look right on square pixels.
+    pub pixel_aspect: (usize, usize),
+    /// Exact display resolution to use, instead of the smallest mode that fits a frame.
+    pub resolution: Option<(usize, usize)>,
+    /// What happens once the last frame has been played.
+    pub end: EndBehavior,
+    /// Number of frames played per second, which should match the rate the
+    /// frames were extracted at.
+    pub fps: u32,
+    /// Whether frames are skipped when playback falls a whole frame behind.
+    pub drop_frames: bool,
+    /// Whether a blend of every two frames is shown between them, doubling the
+    /// framerate where there is time for it. Two-tone render modes never blend.
+    pub interpolate: bool,
+    /// How long the clock is calibrated against the firmware's stall for, in
+    /// milliseconds.
+    pub calibration_ms: u32,
+    /// How the CPU passes the time while waiting between frames.
+    pub idle: IdleStrategy,
+    /// Whether each frame is held until a key is pressed instead of being timed.
+    pub step_mode: bool,
+    /// Index of the frame playback starts at, clamped to the last frame.
+    pub start_frame: usize,
+    /// Whether frames are played backwards, from the last one to the start frame.
+    pub reverse: bool,
+    /// Index of a frame to dump over serial as base64 once it is rendered.
+    pub dump_frame: Option<usize>,
+    /// Whether a checksum of every rendered frame is written over serial.
+    pub checksum: bool,
+    /// Whether the index of the current frame is shown in the top-left corner.
+    pub show_frame_index: bool,
+    /// Index of a frame to show before playback starts, like a boot logo.
+    pub poster_frame: Option<usize>,
+    /// How long the poster frame is held on screen for, in milliseconds.
+    pub poster_hold_ms: u32,
+    /// Whether the firmware's text cursor is hidden so it can't blink over the video.
+    pub hide_cursor: bool,
+    /// How the characters of a frame are turned into colors.
+    pub mode: RenderMode,
+    /// Color characters are shaded in by the tint mode.
+    pub tint: BltPixel,
+    /// Colors the palette mode picks from.
+    pub fixed_palette: &'static [BltPixel],
+    /// Gradient magnitude, from 0 to 255, at which a pixel counts as an edge in
+    /// the edges mode.
+    pub edge_threshold: u8,
+    /// Color drawn for characters darker than the threshold.
+    pub low_color: BltPixel,
+    /// Color drawn for characters at or above the threshold.
+    pub high_color: BltPixel,
+    /// Luminance, from 0 to 255, that splits characters between the two colors.
+    pub threshold: u8,
+    /// Whether the threshold is picked for every frame from its histogram, with
+    /// Otsu's method, falling back to the fixed threshold for single-shade frames.
+    pub auto_threshold: bool,
+    /// Lookup table every color channel is passed through, to gamma correct the
+    /// colors for the panel.
+    pub gamma: &'static [u8; 256],
+    /// Whether the two colors are swapped.
+    pub invert: bool,
+    /// Color the display is cleared to, defaulting to the high color.
+    pub background: Option<BltPixel>,
+}
+
+impl PlaybackConfig {
+    pub const DEFAULT: Self = Self {
+        transform: Transform::None,
+        scale: ScaleMode::None,
+        crop: None,
+        pixel_aspect: (1, 1),
+        resolution: None,
+        end: EndBehavior::Exit,
+        fps: 10,
+        drop_frames: false,
+        interpolate: false,
+        // NOTE: Long enough for the firmware's stall granularity not to matter, short enough not
+        // to noticeably hold up startup
+        calibration_ms: 10,
+        idle: IdleStrategy::Spin,
+        step_mode: false,
+        start_frame: 0,
+        reverse: false,
+        dump_frame: None,
+        checksum: false,
+        show_frame_index: false,
+        poster_frame: None,
+        poster_hold_ms: 3000,
+        hide_cursor: true,
+        mode: RenderMode::Threshold,
+        tint: BltPixel::new(255, 255, 255),
+        // NOTE: The high intensity CGA palette 1
+        fixed_palette: &[
+            BltPixel::new(0, 0, 0),
+            BltPixel::new(85, 255, 255),
+            BltPixel::new(255, 85, 255),
+            BltPixel::new(255, 255, 255),
+        ],
+        edge_threshold: 64,
+        low_color: BltPixel::new(255, 255, 255),
+        high_color: BltPixel::new(34, 34, 34),
+        // NOTE: Only the darkest character, `$`, falls below this
+        threshold: 1,
+        auto_threshold: false,
+        gamma: &IDENTITY_GAMMA,
+        invert: false,
+        background: None,
+    };
+
+    /// Time budget of a single frame, in microseconds.
+    pub const fn frame_interval_us(&self) -> usize {
+        // NOTE: A zero framerate would mean dividing by zero, so it falls back to the default
+        let fps = match self.fps {
+            0 => Self::DEFAULT.fps,
+            fps => fps,
+        };
+
+        1_000_000 / fps as usize
+    }
+
+    /// Index of the frame playback starts at, out of `frame_count`.
+    pub const fn clamped_start_frame(&self, frame_count: usize) -> usize {
+        // NOTE: An out of range start frame is clamped to the last frame rather than playing nothing
+        let last_frame = frame_count.saturating_sub(1);
+        match self.start_frame < last_frame {
+            true => self.start_frame,
+            false => last_frame,
+        }
+    }
+
+    /// Index of the `n`th frame played out of `frame_count`, starting from `start_frame`.
+    pub const fn frame_index(&self, n: usize, start_frame: usize, frame_count: usize) -> usize {
+        // NOTE: Rewinding plays the same frames, just from the last one back down to the start frame
+        match self.reverse {
+            false => start_frame + n,
+            true => frame_count - 1 - n,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_index_forwards_and_reversed() {
+        let forwards = PlaybackConfig::DEFAULT;
+        let reversed = PlaybackConfig {
+            reverse: true,
+            ..PlaybackConfig::DEFAULT
+        };
+
+        // NOTE: Both play the 8 frames from index 2, just in opposite directions
+        assert_eq!(forwards.frame_index(0, 2, 10), 2);
+        assert_eq!(forwards.frame_index(7, 2, 10), 9);
+        assert_eq!(reversed.frame_index(0, 2, 10), 9);
+        assert_eq!(reversed.frame_index(7, 2, 10), 2);
+    }
+
+    #[test]
+    fn frame_interval_follows_fps() {
+        let config = |fps| PlaybackConfig {
+            fps,
+            ..PlaybackConfig::DEFAULT
+        };
+
+        assert_eq!(config(10).frame_interval_us(), 100_000);
+        assert_eq!(config(30).frame_interval_us(), 33_333);
+        assert_eq!(config(60).frame_interval_us(), 16_666);
+    }
+
+    #[test]
+    fn frame_interval_zero_fps_falls_back() {
+        let config = PlaybackConfig {
+            fps: 0,
+            ..PlaybackConfig::DEFAULT
+        };
+
+        assert_eq!(
+            config.frame_interval_us(),
+            PlaybackConfig::DEFAULT.frame_interval_us()
+        );
+    }
+
+    #[test]
+    fn start_frame_clamped() {
+        let config = |start_frame| PlaybackConfig {
+            start_frame,
+            ..PlaybackConfig::DEFAULT
+        };
+
+        assert_eq!(config(0).clamped_start_frame(10), 0);
+        assert_eq!(config(9).clamped_start_frame(10), 9);
+        assert_eq!(config(10).clamped_start_frame(10), 9);
+        assert_eq!(config(usize::MAX).clamped_start_frame(10), 9);
+    }
+
+    #[test]
+    fn start_frame_no_frames() {
+        let config = PlaybackConfig {
+            start_frame: 5,
+            ..PlaybackConfig::DEFAULT
+        };
+
+        assert_eq!(config.clamped_start_frame(0), 0);
+        assert_eq!(PlaybackConfig::DEFAULT.clamped_start_frame(0), 0);
+    }
+
+    #[test]
+    fn end_actions() {
+        let actions = |end: EndBehavior| {
+            let actions = end.actions();
+            (
+                actions.restart,
+                actions.wait_for_key,
+                actions.clear,
+                actions.linger_us,
+            )
+        };
+
+        assert_eq!(actions(EndBehavior::Loop), (true, false, false, 0));
+        assert_eq!(actions(EndBehavior::HoldLastFrame), (false, true, false, 0));
+        assert_eq!(actions(EndBehavior::Clear), (false, false, true, 0));
+        assert_eq!(actions(EndBehavior::Exit), (false, false, false, 1_000_000));
+    }
+}
@@ -0,0 +1,203 @@
+use alloc::{string::String, vec::Vec};
+use core::fmt::Write;
+
+use uefi::{table::boot::BootServices, Status};
+
+use crate::{
+    clock::{self, Clock, FrameClock},
+    config::PlaybackConfig,
+    input,
+    render::{self, HEIGHT, WIDTH},
+};
+
+// NOTE: Much shorter than the ramp frames are generated with, since the console
+// downsamples them far enough that fine shading turns into noise
+const CONSOLE_RAMP: &[u8] = b" .:-=+*#%@";
+
+/// Picks the console character for a `luminance`. Light text on a dark console
+/// stands in for the low color on the high one, so like `render::palette` dark
+/// characters are dense and bright ones sparse, unless inverted.
+fn console_char(luminance: u8, invert: bool) -> char {
+    let density = match invert {
+        false => u8::MAX - luminance,
+        true => luminance,
+    };
+
+    CONSOLE_RAMP[density as usize * CONSOLE_RAMP.len() / 256] as char
+}
+
+/// Position in an untransformed frame which the console cell at `(column, row)`
+/// shows, out of a `columns`x`rows` grid stretched over the transformed frame.
+fn source_position(
+    config: &PlaybackConfig,
+    (column, row): (usize, usize),
+    (columns, rows): (usize, usize),
+) -> (usize, usize) {
+    let (frame_width, frame_height) = config.transform.dims(WIDTH, HEIGHT);
+    let (x, y) = (column * frame_width / columns, row * frame_height / rows);
+
+    config
+        .transform
+        .inverse()
+        .apply(x, y, frame_width, frame_height)
+}
+
+/// Writes `index` to the top-left corner of the screen through the firmware console.
+pub fn draw_frame_index(index: usize) {
+    let mut system_table = uefi::helpers::system_table();
+    let stdout = system_table.stdout();
+
+    // NOTE: This is only a debugging aid, so it is fine for it to fail on odd consoles
+    let _ = stdout.set_cursor_position(0, 0);
+    let _ = write!(stdout, "frame {index:>5}");
+}
+
+/// Plays `frames` as text through the firmware console, as a fallback when there
+/// is no GOP to draw to. Timing, the start frame, rewinding, frame dropping, step
+/// mode, pausing, the frame index and the transform all work like they do on GOP,
+/// but the render mode, colors, crop window, pixel aspect, scaling and anything
+/// written over serial don't apply.
+pub fn play(boot_services: &BootServices, config: &PlaybackConfig, frames: &[&str]) -> Status {
+    let mut system_table = uefi::helpers::system_table();
+    let stdout = system_table.stdout();
+
+    let Ok(Some(mode)) = stdout.current_mode() else {
+        return Status::UNSUPPORTED;
+    };
+
+    // NOTE: Writing to the last column or row can make the console wrap or scroll
+    let (columns, rows) = (
+        mode.columns().saturating_sub(1),
+        mode.rows().saturating_sub(1),
+    );
+
+    let start_frame = config.clamped_start_frame(frames.len());
+    let clock = Clock::calibrate(boot_services, config.calibration_ms as u64 * 1000);
+    let mut line = String::with_capacity(columns);
+
+    loop {
+        let mut frame_clock = FrameClock::new(config.frame_interval_us() as u64, clock.now_us());
+
+        for n in 0..frames.len() - start_frame {
+            let index = config.frame_index(n, start_frame, frames.len());
+            if config.drop_frames && !config.step_mode && frame_clock.is_behind(n, clock.now_us()) {
+                continue;
+            }
+
+            let frame_matrix = frames[index]
+                .split('\n')
+                .map(str::as_bytes)
+                .collect::<Vec<_>>();
+
+            for row in 0..rows {
+                line.clear();
+
+                for column in 0..columns {
+                    // NOTE: Short rows are drawn as blank, like untouched pixels on GOP
+                    let (x, y) = source_position(config, (column, row), (columns, rows));
+                    let x_pixel = frame_matrix
+                        .get(y)
+                        .and_then(|x_pixels| x_pixels.get(x))
+                        .copied()
+                        .unwrap_or(b' ');
+                    line.push(console_char(render::luminance(x_pixel), config.invert));
+                }
+
+                let _ = stdout.set_cursor_position(0, row);
+                let _ = stdout.write_str(&line);
+            }
+
+            if config.show_frame_index {
+                draw_frame_index(index);
+            }
+
+            frame_clock.delay_by(input::hold(boot_services, config, &clock));
+
+            // NOTE: Step mode waits for a key press instead, which already happened above
+            let delay = frame_clock.next_delay(n + 1, clock.now_us());
+            if let (Some(delay), false) = (delay, config.step_mode) {
+                clock::wait_us(boot_services, delay, config.idle);
+            }
+        }
+
+        let end = config.end.actions();
+        if end.restart {
+            continue;
+        }
+        if end.wait_for_key {
+            input::wait_key();
+        }
+        if end.clear {
+            let _ = stdout.clear();
+        }
+        if end.linger_us > 0 {
+            clock::wait_us(boot_services, end.linger_us, config.idle);
+        }
+
+        return Status::SUCCESS;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::Transform;
+
+    #[test]
+    fn console_char_orientation() {
+        assert_eq!(console_char(0, false), '@');
+        assert_eq!(console_char(u8::MAX, false), ' ');
+        assert_eq!(console_char(0, true), ' ');
+        assert_eq!(console_char(u8::MAX, true), '@');
+    }
+
+    #[test]
+    fn console_char_covers_ramp() {
+        // NOTE: Going from bright to dark walks the whole ramp in order, without skipping any
+        let chars = (0..=u8::MAX)
+            .rev()
+            .map(|luminance| console_char(luminance, false) as u8)
+            .collect::<Vec<_>>();
+        let mut ramp = chars.clone();
+        ramp.dedup();
+
+        assert_eq!(ramp, CONSOLE_RAMP);
+    }
+
+    #[test]
+    fn source_position_follows_transform() {
+        let config = |transform| PlaybackConfig {
+            transform,
+            ..PlaybackConfig::DEFAULT
+        };
+        let grid = (WIDTH, HEIGHT);
+        let rotated_grid = (HEIGHT, WIDTH);
+
+        assert_eq!(
+            source_position(&config(Transform::None), (0, 0), grid),
+            (0, 0)
+        );
+        assert_eq!(
+            source_position(&config(Transform::Rotate180), (0, 0), grid),
+            (WIDTH - 1, HEIGHT - 1)
+        );
+        assert_eq!(
+            source_position(&config(Transform::FlipH), (1, 2), grid),
+            (WIDTH - 2, 2)
+        );
+        // NOTE: The top-left of a clockwise rotated frame is the bottom-left of the source
+        assert_eq!(
+            source_position(&config(Transform::Rotate90CW), (0, 0), rotated_grid),
+            (0, HEIGHT - 1)
+        );
+    }
+
+    #[test]
+    fn source_position_downsamples() {
+        let config = PlaybackConfig::DEFAULT;
+
+        // NOTE: A half size grid skips every other character in both directions
+        let half_grid = (WIDTH / 2, HEIGHT / 2);
+        assert_eq!(source_position(&config, (3, 4), half_grid), (6, 8));
+    }
+}
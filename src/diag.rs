@@ -0,0 +1,103 @@
+use core::fmt;
+
+use uefi::{proto::console::gop::PixelFormat, CStr16};
+
+/// One-shot startup report, written over serial for debugging on real hardware.
+pub struct Diagnostics<'a> {
+    /// Vendor of the firmware, as reported by the system table.
+    pub firmware_vendor: &'a CStr16,
+    /// Vendor-specific revision of the firmware.
+    pub firmware_revision: u32,
+    /// Number of modes the firmware offered.
+    pub mode_count: usize,
+    /// Resolution of the chosen display mode.
+    pub resolution: (usize, usize),
+    /// Pixel format of the chosen display mode.
+    pub pixel_format: PixelFormat,
+    /// Pixels per scanline of the chosen display mode.
+    pub stride: usize,
+    /// Number of frames that will be played.
+    pub frame_count: usize,
+    /// Factor frames are upscaled by.
+    pub scale: usize,
+}
+
+impl fmt::Display for Diagnostics<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (width, height) = self.resolution;
+
+        writeln!(
+            f,
+            "diag: firmware {} revision {:#x}",
+            self.firmware_vendor, self.firmware_revision
+        )?;
+        writeln!(f, "diag: {} display modes available", self.mode_count)?;
+        writeln!(
+            f,
+            "diag: display mode {width}x{height}, {:?}, stride {}",
+            self.pixel_format, self.stride
+        )?;
+        writeln!(
+            f,
+            "diag: {} frames, scaled {}x",
+            self.frame_count, self.scale
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uefi::cstr16;
+
+    use super::*;
+
+    fn sample() -> Diagnostics<'static> {
+        Diagnostics {
+            firmware_vendor: cstr16!("EDK II"),
+            firmware_revision: 0x10000,
+            mode_count: 30,
+            resolution: (1024, 768),
+            pixel_format: PixelFormat::Bgr,
+            stride: 1024,
+            frame_count: 2180,
+            scale: 2,
+        }
+    }
+
+    #[test]
+    fn diagnostics_format() {
+        assert_eq!(
+            sample().to_string(),
+            "diag: firmware EDK II revision 0x10000\n\
+             diag: 30 display modes available\n\
+             diag: display mode 1024x768, Bgr, stride 1024\n\
+             diag: 2180 frames, scaled 2x\n"
+        );
+    }
+
+    #[test]
+    fn diagnostics_firmware_line() {
+        let diagnostics = Diagnostics {
+            firmware_vendor: cstr16!("American Megatrends"),
+            firmware_revision: 0x5001a,
+            ..sample()
+        };
+
+        let report = diagnostics.to_string();
+        assert_eq!(
+            report.lines().next(),
+            Some("diag: firmware American Megatrends revision 0x5001a")
+        );
+
+        // NOTE: The revision is vendor-specific, so a zero is still written out as hex
+        let report = Diagnostics {
+            firmware_revision: 0,
+            ..sample()
+        }
+        .to_string();
+        assert_eq!(
+            report.lines().next(),
+            Some("diag: firmware EDK II revision 0x0")
+        );
+    }
+}
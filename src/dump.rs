@@ -0,0 +1,114 @@
+//! Streams a rendered frame over serial as base64 encoded RGB bytes, which can
+//! be reconstructed on the host with something like
+//! `Image.frombytes("RGB", (width, height), base64.b64decode(data))`.
+
+use core::fmt::{self, Write};
+
+use uefi::proto::console::gop::BltPixel;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// NOTE: 19 pixels are 57 bytes of RGB, which encode to a 76 character base64 line
+const PIXELS_PER_LINE: usize = 19;
+
+// NOTE: 32-bit FNV-1a, which is cheap enough to run over every frame
+const FNV_OFFSET_BASIS: u32 = 0x811c_9dc5;
+const FNV_PRIME: u32 = 0x0100_0193;
+
+/// Encodes up to 3 bytes into 4 base64 characters, padding any missing bytes.
+fn encode_chunk(chunk: &[u8]) -> [u8; 4] {
+    let byte = |i: usize| chunk.get(i).copied().unwrap_or(0) as u32;
+    let bits = (byte(0) << 16) | (byte(1) << 8) | byte(2);
+
+    let mut out = [b'='; 4];
+    for (i, char) in out.iter_mut().enumerate().take(chunk.len() + 1) {
+        *char = ALPHABET[(bits >> (18 - 6 * i)) as usize & 0x3f];
+    }
+
+    out
+}
+
+/// Writes the `width`x`height` frame in `pixels` to `out`, framed by `BEGIN` and
+/// `END` marker lines.
+pub fn dump_frame(
+    out: &mut impl Write,
+    index: usize,
+    pixels: &[BltPixel],
+    (width, height): (usize, usize),
+) -> fmt::Result {
+    writeln!(out, "BEGIN FRAME {index} {width}x{height} RGB")?;
+
+    for line in pixels.chunks(PIXELS_PER_LINE) {
+        let mut bytes = [0; PIXELS_PER_LINE * 3];
+        for (rgb, pixel) in bytes.chunks_mut(3).zip(line) {
+            rgb.copy_from_slice(&[pixel.red, pixel.green, pixel.blue]);
+        }
+
+        for chunk in bytes[..line.len() * 3].chunks(3) {
+            let encoded = encode_chunk(chunk);
+
+            // NOTE: The base64 alphabet is all ASCII, so this can never fail
+            out.write_str(core::str::from_utf8(&encoded).unwrap())?;
+        }
+
+        out.write_char('\n')?;
+    }
+
+    writeln!(out, "END FRAME {index}")
+}
+
+/// Checksum of the RGB bytes of `pixels`, for comparing rendered frames against
+/// a known-good run without dumping them whole.
+pub fn checksum(pixels: &[BltPixel]) -> u32 {
+    pixels
+        .iter()
+        .flat_map(|pixel| [pixel.red, pixel.green, pixel.blue])
+        .fold(FNV_OFFSET_BASIS, |hash, byte| {
+            (hash ^ byte as u32).wrapping_mul(FNV_PRIME)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_chunk_vectors() {
+        assert_eq!(&encode_chunk(b"Man"), b"TWFu");
+        assert_eq!(&encode_chunk(b"Ma"), b"TWE=");
+        assert_eq!(&encode_chunk(b"M"), b"TQ==");
+    }
+
+    #[test]
+    fn dump_frame_framing() {
+        let pixels = vec![BltPixel::new(0x4d, 0x61, 0x6e); PIXELS_PER_LINE + 1];
+
+        let mut out = String::new();
+        dump_frame(&mut out, 7, &pixels, (PIXELS_PER_LINE + 1, 1)).unwrap();
+
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "BEGIN FRAME 7 20x1 RGB");
+        assert_eq!(lines[1], "TWFu".repeat(PIXELS_PER_LINE));
+        assert_eq!(lines[1].len(), 76);
+        assert_eq!(lines[2], "TWFu");
+        assert_eq!(lines[3], "END FRAME 7");
+    }
+
+    #[test]
+    fn checksum_known_buffer() {
+        assert_eq!(checksum(&[]), FNV_OFFSET_BASIS);
+
+        // NOTE: FNV-1a of the bytes "abc"
+        assert_eq!(checksum(&[BltPixel::new(b'a', b'b', b'c')]), 0x1a47_e90b);
+    }
+
+    #[test]
+    fn checksum_changes_with_one_pixel() {
+        let mut pixels = vec![BltPixel::new(34, 34, 34); 64];
+        let before = checksum(&pixels);
+
+        pixels[37] = BltPixel::new(255, 255, 255);
+        assert_ne!(checksum(&pixels), before);
+    }
+}
@@ -0,0 +1,81 @@
+//! Splitting of `ascii.txt` into frames, and checking them. This is built into `build.rs`, and into
+//! the crate only for its tests, so it may use nothing but `core` and `alloc`.
+
+use alloc::vec::Vec;
+
+/// What `generate_ascii_art.py` writes between frames.
+pub const SEPARATOR: &str = "SPLIT";
+
+/// Splits `ascii` into its frames, stopping after the first `max_frames` of them
+/// when set.
+pub fn split(ascii: &str, max_frames: Option<usize>) -> Vec<&str> {
+    ascii
+        .split(SEPARATOR)
+        .take(max_frames.unwrap_or(usize::MAX))
+        .collect()
+}
+
+/// Drops the last of `frames` if it is shorter than the first, which is what an
+/// interrupted `generate_ascii_art.py` leaves behind. Returns whether it was
+/// dropped, or an error instead of dropping it when `strict`.
+pub fn drop_truncated(frames: &mut Vec<&str>, strict: bool) -> Result<bool, &'static str> {
+    // NOTE: All frames are otherwise the same size, so dropping the last one still leaves every
+    // complete frame to play
+    let [first, .., last] = frames.as_slice() else {
+        return Ok(false);
+    };
+    if last.len() >= first.len() {
+        return Ok(false);
+    }
+
+    if strict {
+        return Err(
+            "last frame in ascii.txt is truncated, regenerate it with generate_ascii_art.py",
+        );
+    }
+
+    frames.pop();
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_stops_at_max_frames() {
+        let ascii = "aaSPLITbbSPLITccSPLITdd";
+
+        assert_eq!(split(ascii, Some(2)), ["aa", "bb"]);
+        assert_eq!(split(ascii, Some(1)), ["aa"]);
+        assert_eq!(split(ascii, Some(10)), ["aa", "bb", "cc", "dd"]);
+        assert_eq!(split(ascii, None), ["aa", "bb", "cc", "dd"]);
+    }
+
+    #[test]
+    fn drop_truncated_last_frame() {
+        let mut frames = split("aaaSPLITbbbSPLITc", None);
+
+        assert_eq!(drop_truncated(&mut frames, false), Ok(true));
+        assert_eq!(frames, ["aaa", "bbb"]);
+    }
+
+    #[test]
+    fn drop_truncated_strict() {
+        let mut frames = split("aaaSPLITbbbSPLITc", None);
+
+        assert!(drop_truncated(&mut frames, true).is_err());
+        assert_eq!(frames, ["aaa", "bbb", "c"]);
+    }
+
+    #[test]
+    fn drop_truncated_keeps_complete_frames() {
+        let mut frames = split("aaaSPLITbbbSPLITccc", None);
+        assert_eq!(drop_truncated(&mut frames, true), Ok(false));
+        assert_eq!(frames, ["aaa", "bbb", "ccc"]);
+
+        let mut frames = split("a", None);
+        assert_eq!(drop_truncated(&mut frames, true), Ok(false));
+        assert_eq!(frames, ["a"]);
+    }
+}
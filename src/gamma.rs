@@ -0,0 +1,41 @@
+//! Gamma lookup tables. This is built into `build.rs`, since `core` has no `powf`
+//! to build them with at runtime, and into the crate only for its tests.
+
+/// Lookup table raising every color channel to the power of `gamma`, which
+/// leaves pure black and white as they are.
+pub fn lut(gamma: f64) -> [u8; 256] {
+    let mut lut = [0; 256];
+    for (i, entry) in lut.iter_mut().enumerate() {
+        *entry = (255.0 * (i as f64 / 255.0).powf(gamma)).round() as u8;
+    }
+
+    lut
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lut_keeps_black_and_white() {
+        for gamma in [0.5, 1.0, 2.2] {
+            let lut = lut(gamma);
+
+            assert_eq!(lut[0], 0);
+            assert_eq!(lut[255], 255);
+        }
+    }
+
+    #[test]
+    fn lut_midpoint() {
+        assert_eq!(lut(1.0)[128], 128);
+        // NOTE: 255 * (128 / 255)^2.2 and 255 * (128 / 255)^0.5
+        assert_eq!(lut(2.2)[128], 56);
+        assert_eq!(lut(0.5)[128], 181);
+    }
+
+    #[test]
+    fn lut_identity() {
+        assert_eq!(lut(1.0), crate::render::IDENTITY_GAMMA);
+    }
+}
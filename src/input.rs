@@ -0,0 +1,49 @@
+use uefi::{proto::console::text::Key, table::boot::BootServices};
+
+use crate::{
+    clock::{self, Clock},
+    config::PlaybackConfig,
+};
+
+/// Returns the character of the next pending key press, if there is one.
+///
+/// This goes through the firmware console, which on most firmware also forwards
+/// bytes received over serial.
+pub fn poll_key() -> Option<char> {
+    let mut system_table = uefi::helpers::system_table();
+
+    match system_table.stdin().read_key() {
+        Ok(Some(Key::Printable(key))) => Some(key.into()),
+        _ => None,
+    }
+}
+
+/// Blocks until a key is pressed, returning its character like [`poll_key`].
+pub fn wait_key() -> Option<char> {
+    let mut system_table = uefi::helpers::system_table();
+
+    if let Some(event) = system_table.stdin().wait_for_key_event() {
+        // NOTE: If waiting fails, the poll below just doesn't find a key
+        let _ = system_table.boot_services().wait_for_event(&mut [event]);
+    }
+
+    poll_key()
+}
+
+/// Holds the frame that was just shown, until any key is pressed in step mode,
+/// or until `v` is pressed again if it was pressed to pause. Returns how long
+/// playback was held for, in microseconds, to push the schedule back by.
+pub fn hold(boot_services: &BootServices, config: &PlaybackConfig, clock: &Clock) -> u64 {
+    let held_at = clock.now_us();
+
+    if config.step_mode {
+        // NOTE: Every key press, on the keyboard or over serial, advances a single frame
+        wait_key();
+    } else if poll_key() == Some('v') {
+        while poll_key() != Some('v') {
+            clock::wait_us(boot_services, 10_000, config.idle);
+        }
+    }
+
+    clock.now_us() - held_at
+}
@@ -1,87 +1,413 @@
-#![no_main]
-#![no_std]
+#![cfg_attr(not(test), no_main)]
+#![cfg_attr(not(test), no_std)]
 
 extern crate alloc;
+
+mod clock;
+mod config;
+mod console;
+#[cfg(feature = "diag")]
+mod diag;
+mod dump;
+// NOTE: Only used by `build.rs`, these are built in for their tests
+#[cfg(test)]
+mod frames;
+#[cfg(test)]
+mod gamma;
+mod input;
+mod options;
+#[cfg(feature = "qemu")]
+mod qemu;
+mod render;
+#[cfg(feature = "selftest")]
+mod selftest;
+mod serial;
+#[cfg(feature = "trace")]
+mod trace;
+
 use alloc::vec;
 use alloc::vec::Vec;
 
 use uefi::{
-    entry, println,
+    println,
     proto::console::gop::{BltOp, BltPixel, BltRegion, GraphicsOutput},
     table::{Boot, SystemTable},
     Handle, Status,
 };
 
-include!(concat!(env!("OUT_DIR"), "/ascii.rs"));
+use clock::{Clock, FrameClock};
+use config::CONFIG;
+use render::{ScaleMode, HEIGHT, WIDTH};
 
-const WIDTH: usize = 300;
-const HEIGHT: usize = 240;
+include!(concat!(env!("OUT_DIR"), "/ascii.rs"));
 
 #[allow(unreachable_code)]
-#[entry]
-fn main(_image_handle: Handle, mut system_table: SystemTable<Boot>) -> Status {
+#[cfg_attr(not(test), uefi::entry)]
+fn main(image_handle: Handle, mut system_table: SystemTable<Boot>) -> Status {
     uefi::helpers::init(&mut system_table).unwrap();
+    system_table
+        .stdout()
+        .clear()
+        .expect("failed to clear stdout");
+
+    // NOTE: Flags passed from the shell win over the build-time config, so a single build can
+    // be tried out with different settings
+    let mut config = CONFIG;
+    if let Some(options) = options::read(system_table.boot_services(), image_handle) {
+        for rejected in options::apply(&options, &mut config) {
+            println!("unknown or invalid option {rejected}, ignoring it");
+        }
+    }
     let stdout = system_table.stdout();
-    stdout.clear().expect("failed to clear stdout");
+
+    // NOTE: This has to happen while the console is still ours through boot services, and
+    // before the mode switch below. Not all firmware supports hiding the cursor, which
+    // only costs a blinking cursor, so failures are ignored
+    if config.hide_cursor {
+        let _ = stdout.enable_cursor(false);
+    }
 
     let boot_services = system_table.boot_services();
 
-    let gop_handle = boot_services
-        .get_handle_for_protocol::<GraphicsOutput>()
-        .expect("failed to get GOP handle");
+    let frames = &ASCII_FRAMES[..ASCII_FRAMES.len().min(2180)];
+
+    let Ok(gop_handle) = boot_services.get_handle_for_protocol::<GraphicsOutput>() else {
+        // NOTE: Without GOP there is still the firmware console, which frames can be drawn to as text
+        return finish(console::play(boot_services, &config, frames));
+    };
     let mut gop = boot_services
         .open_protocol_exclusive::<GraphicsOutput>(gop_handle)
         .expect("failed to open GOP");
 
-    let mut modes = gop.modes(boot_services).collect::<Vec<_>>();
-    modes.sort_by_key(|x| x.info().resolution());
-    let smallest_mode = modes.first().unwrap();
-    gop.set_mode(smallest_mode).expect("failed to set GOP mode");
+    let (frame_width, frame_height) = config.transform.dims(WIDTH, HEIGHT);
+
+    // NOTE: A crop window which doesn't fit inside a frame is ignored rather than guessed at
+    let crop_window = config.crop.filter(|&(x, y, width, height)| {
+        let fits = x + width <= frame_width && y + height <= frame_height;
+        if !fits {
+            println!(
+                "crop window doesn't fit in a {frame_width}x{frame_height} frame, ignoring it"
+            );
+        }
+        fits
+    });
+    let (view_width, view_height) = crop_window
+        .map_or((frame_width, frame_height), |(_, _, width, height)| {
+            (width, height)
+        });
+
+    // NOTE: The pixel aspect is given for the source, so rotating the frame rotates it too
+    let (aspect_x, aspect_y) = config
+        .transform
+        .dims(config.pixel_aspect.0, config.pixel_aspect.1);
+    let (corrected_width, corrected_height) =
+        render::correct_aspect((view_width, view_height), (aspect_x, aspect_y));
+
+    let modes = gop.modes(boot_services).collect::<Vec<_>>();
+    let resolutions = modes
+        .iter()
+        .map(|mode| mode.info().resolution())
+        .collect::<Vec<_>>();
+
+    let needed = (corrected_width, corrected_height);
+    let crop = config.scale == ScaleMode::CenterCrop;
+    let Some(mode) = render::pick_mode(&resolutions, needed, crop, config.resolution) else {
+        match config.resolution {
+            Some((width, height)) if !resolutions.contains(&(width, height)) => {
+                println!("no {width}x{height} display mode is offered")
+            }
+            Some((width, height)) => println!(
+                "{width}x{height} is too small for a {corrected_width}x{corrected_height} frame"
+            ),
+            None => println!(
+                "no display mode is large enough for a {corrected_width}x{corrected_height} frame"
+            ),
+        }
+        return finish(Status::UNSUPPORTED);
+    };
+    gop.set_mode(&modes[mode]).expect("failed to set GOP mode");
 
     let (width, height) = gop.current_mode_info().resolution();
 
+    // NOTE: Clearing the whole display once means the margins around a frame never need drawing
+    let default_pixel = render::background(&config);
+    gop.blt(BltOp::VideoFill {
+        color: default_pixel,
+        dest: (0, 0),
+        dims: (width, height),
+    })
+    .expect("failed to clear display");
+
     println!("scaled resolution to {width}x{height}");
 
-    let mut default_pixel = BltPixel::new(34, 34, 34);
-    for frame in ASCII_FRAMES.iter().take(2180) {
-        let mut pixbuf = vec![default_pixel; WIDTH * HEIGHT];
-        let frame_matrix = frame
-            .split('\n')
-            .map(|str| str.as_bytes())
-            .collect::<Vec<_>>();
-
-        for (y, x_pixels) in frame_matrix.iter().enumerate() {
-            for (x, x_pixel) in (*x_pixels).iter().enumerate() {
-                // NOTE: Just provide a placebo pixel so that we don't panic
-                // NOTE: `y * WIDTH + x` is just normalizing the matrix indices into a 1D array index
-                let real_pixel = pixbuf.get_mut(y * WIDTH + x).unwrap_or(&mut default_pixel);
-
-                // TODO: Handle all the different ASCII chars with different colors
-                if *x_pixel == b'$' {
-                    // Background, white
-                    real_pixel.red = 255;
-                    real_pixel.blue = 255;
-                    real_pixel.green = 255;
-                } else {
-                    // Foreground, lighter shade of black
-                    real_pixel.red = 34;
-                    real_pixel.green = 34;
-                    real_pixel.blue = 34;
+    #[cfg(feature = "selftest")]
+    return finish(selftest::run(boot_services, &config, &mut gop));
+
+    let start_frame = config.clamped_start_frame(frames.len());
+
+    let scale = config
+        .scale
+        .factor((corrected_width, corrected_height), (width, height));
+    let scaled_dims = (corrected_width * scale, corrected_height * scale);
+    let mut scaled = match scaled_dims == (view_width, view_height) {
+        true => Vec::new(),
+        false => vec![default_pixel; scaled_dims.0 * scaled_dims.1],
+    };
+
+    // NOTE: Opening the port probes it, so it is opened once for everything written over serial,
+    // and only when something is
+    let wants_serial = cfg!(feature = "diag")
+        || cfg!(feature = "trace")
+        || config.checksum
+        || config.dump_frame.is_some();
+    let mut serial_port = match wants_serial {
+        true => serial::open(boot_services),
+        false => None,
+    };
+
+    #[cfg(feature = "diag")]
+    {
+        use core::fmt::Write;
+
+        let info = gop.current_mode_info();
+        // NOTE: The firmware identity lives in the system table, which is only fully usable
+        // while boot services are still active, like everything else here
+        let diagnostics = diag::Diagnostics {
+            firmware_vendor: system_table.firmware_vendor(),
+            firmware_revision: system_table.firmware_revision(),
+            mode_count: modes.len(),
+            resolution: info.resolution(),
+            pixel_format: info.pixel_format(),
+            stride: info.stride(),
+            frame_count: frames.len(),
+            scale,
+        };
+
+        match &mut serial_port {
+            Some(serial) => write!(serial, "{diagnostics}").expect("failed to write diagnostics"),
+            None => println!("no serial port to write diagnostics to"),
+        }
+    }
+
+    let mut cropped = match crop_window {
+        Some(_) => vec![default_pixel; view_width * view_height],
+        None => Vec::new(),
+    };
+
+    let mut present = |pixbuf: &[BltPixel]| {
+        let pixbuf = match crop_window {
+            Some(window) => {
+                render::crop(pixbuf, frame_width, window, &mut cropped);
+                &cropped
+            }
+            None => pixbuf,
+        };
+
+        if scaled_dims == (view_width, view_height) {
+            draw_centered(&mut gop, pixbuf, scaled_dims);
+            return;
+        }
+
+        // NOTE: Square pixels scale by a whole factor, which block replication does faster
+        if (corrected_width, corrected_height) == (view_width, view_height) {
+            render::upscale_integer(pixbuf, (view_width, view_height), scale, &mut scaled);
+        } else {
+            render::resize_nearest(pixbuf, (view_width, view_height), &mut scaled, scaled_dims);
+        }
+
+        draw_centered(&mut gop, &scaled, scaled_dims);
+    };
+
+    // NOTE: Every frame is the same size, so a single buffer is reused for all of them, along
+    // with whatever scratch space the render mode needs
+    let mut pixbuf = vec![default_pixel; frame_width * frame_height];
+    let mut edge_buffers = render::EdgeBuffers::new(config.mode);
+
+    // NOTE: Two-tone frames would only blend into a muddy third color, and step mode has no
+    // time between frames to fill, so neither is interpolated
+    let interpolate = config.interpolate && !config.mode.is_two_tone() && !config.step_mode;
+    let (mut next_pixbuf, mut blended) = match interpolate {
+        true => (pixbuf.clone(), pixbuf.clone()),
+        false => (Vec::new(), Vec::new()),
+    };
+
+    if let Some(poster) = config.poster_frame.and_then(|index| frames.get(index)) {
+        render::render_frame(poster, &config, &mut pixbuf, &mut edge_buffers);
+        present(&pixbuf);
+
+        clock::wait_us(
+            boot_services,
+            config.poster_hold_ms as u64 * 1000,
+            config.idle,
+        );
+    }
+
+    #[cfg(feature = "trace")]
+    if let Some(serial) = &mut serial_port {
+        let _ = trace::write_line(&mut **serial, trace::HEADER);
+    }
+
+    if config.checksum && serial_port.is_none() {
+        println!("no serial port to write checksums to");
+    }
+
+    let clock = Clock::calibrate(boot_services, config.calibration_ms as u64 * 1000);
+    loop {
+        let mut frame_clock = FrameClock::new(config.frame_interval_us() as u64, clock.now_us());
+        let mut rendered_next = None;
+
+        for n in 0..frames.len() - start_frame {
+            let index = config.frame_index(n, start_frame, frames.len());
+            let frame = frames[index];
+
+            // NOTE: Skipping a frame that is already a whole interval late keeps playback in sync,
+            // at the cost of smoothness. A frame that is meant to be dumped is never dropped
+            if config.drop_frames
+                && !config.step_mode
+                && config.dump_frame != Some(index)
+                && frame_clock.is_behind(n, clock.now_us())
+            {
+                continue;
+            }
+
+            #[cfg(feature = "trace")]
+            let render_start = clock.now_us();
+
+            // NOTE: Short rows leave pixels untouched, so they have to be reset for every frame.
+            // A frame which was already rendered to blend with is used as it is
+            if rendered_next == Some(index) {
+                core::mem::swap(&mut pixbuf, &mut next_pixbuf);
+            } else {
+                pixbuf.fill(default_pixel);
+                render::render_frame(frame, &config, &mut pixbuf, &mut edge_buffers);
+            }
+
+            #[cfg(feature = "trace")]
+            let render_us = clock.now_us() - render_start;
+
+            if let (true, Some(serial)) = (config.checksum, &mut serial_port) {
+                use core::fmt::Write;
+
+                let checksum = dump::checksum(&pixbuf);
+                writeln!(serial, "CHECKSUM {index} {checksum:08x}")
+                    .expect("failed to write checksum over serial");
+            }
+
+            if config.dump_frame == Some(index) {
+                match &mut serial_port {
+                    Some(serial) => {
+                        dump::dump_frame(&mut **serial, index, &pixbuf, (frame_width, frame_height))
+                            .expect("failed to dump frame over serial")
+                    }
+                    None => println!("no serial port to dump frame {index} to"),
+                }
+            }
+
+            // NOTE: A late frame is presented straight away, the ones after it still keep to
+            // the schedule and so catch back up. Step mode waits for a key press instead
+            let delay = frame_clock.next_delay(n, clock.now_us());
+            if let (Some(delay), false) = (delay, config.step_mode) {
+                clock::wait_us(boot_services, delay, config.idle);
+            }
+
+            #[cfg(feature = "trace")]
+            let draw_start = clock.now_us();
+
+            present(&pixbuf);
+
+            #[cfg(feature = "trace")]
+            if let Some(serial) = &mut serial_port {
+                let frame_trace = trace::FrameTrace {
+                    index,
+                    intended_us: frame_clock.due_us(n),
+                    actual_us: draw_start,
+                    render_us,
+                    draw_us: clock.now_us() - draw_start,
+                };
+
+                // NOTE: Losing a line of the trace isn't worth stopping playback over
+                let _ = trace::write_line(&mut **serial, frame_trace);
+            }
+
+            if config.show_frame_index {
+                console::draw_frame_index(index);
+            }
+
+            frame_clock.delay_by(input::hold(boot_services, &config, &clock));
+
+            // NOTE: The blend is only shown if that can happen before the next frame is due
+            if interpolate && n + 1 < frames.len() - start_frame {
+                let next_index = config.frame_index(n + 1, start_frame, frames.len());
+                next_pixbuf.fill(default_pixel);
+                render::render_frame(
+                    frames[next_index],
+                    &config,
+                    &mut next_pixbuf,
+                    &mut edge_buffers,
+                );
+                rendered_next = Some(next_index);
+
+                render::blend_frames(&pixbuf, &next_pixbuf, &mut blended);
+                if let Some(delay) = frame_clock.halfway_us(n).checked_sub(clock.now_us()) {
+                    clock::wait_us(boot_services, delay, config.idle);
+                }
+                if !frame_clock.is_behind(n, clock.now_us()) {
+                    present(&blended);
                 }
             }
         }
 
-        gop.blt(BltOp::BufferToVideo {
-            buffer: &pixbuf,
-            src: BltRegion::Full,
-            dest: ((width - WIDTH) / 2, (height - HEIGHT) / 2),
-            dims: (WIDTH, HEIGHT),
-        })
-        .expect("failed to transfer blocks");
+        if !config.end.actions().restart {
+            break;
+        }
+    }
 
-        system_table.boot_services().stall(93709);
+    let end = config.end.actions();
+    if end.wait_for_key {
+        input::wait_key();
+    }
+    if end.clear {
+        gop.blt(BltOp::VideoFill {
+            color: default_pixel,
+            dest: (0, 0),
+            dims: (width, height),
+        })
+        .expect("failed to clear display");
     }
+    if end.linger_us > 0 {
+        clock::wait_us(boot_services, end.linger_us, config.idle);
+    }
+
+    finish(Status::SUCCESS)
+}
+
+/// Hands `status` back to the firmware, reporting it to the host first when
+/// running under QEMU.
+fn finish(status: Status) -> Status {
+    #[cfg(feature = "qemu")]
+    qemu::exit(status);
+
+    status
+}
+
+/// Transfers a `width`x`height` `pixbuf` to the center of the display, cropping
+/// it evenly on any side that doesn't fit.
+fn draw_centered(gop: &mut GraphicsOutput, pixbuf: &[BltPixel], (width, height): (usize, usize)) {
+    let (display_width, display_height) = gop.current_mode_info().resolution();
+    let (coords, (crop_width, crop_height)) =
+        render::center_crop((width, height), (display_width, display_height));
 
-    boot_services.stall(1_000_000);
-    Status::SUCCESS
+    gop.blt(BltOp::BufferToVideo {
+        buffer: pixbuf,
+        src: BltRegion::SubRectangle {
+            coords,
+            px_stride: width,
+        },
+        dest: (
+            (display_width - crop_width) / 2,
+            (display_height - crop_height) / 2,
+        ),
+        dims: (crop_width, crop_height),
+    })
+    .expect("failed to transfer blocks");
 }
@@ -0,0 +1,124 @@
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use uefi::{proto::loaded_image::LoadedImage, table::boot::BootServices, Handle};
+
+use crate::config::{EndBehavior, PlaybackConfig};
+
+/// Reads the load options the image was started with, which the UEFI shell
+/// fills with its command line.
+pub fn read(boot_services: &BootServices, image_handle: Handle) -> Option<String> {
+    let loaded_image = boot_services
+        .open_protocol_exclusive::<LoadedImage>(image_handle)
+        .ok()?;
+
+    // NOTE: Boot entries can carry binary load options, which aren't flags and are ignored
+    let options = loaded_image.load_options_as_cstr16().ok()?;
+    Some(options.to_string())
+}
+
+/// Folds the `--flag` and `--flag=value` arguments in `options` into `config`,
+/// keeping the build-time setting for anything missing or invalid. Returns the
+/// arguments which were rejected, for the caller to report.
+pub fn apply<'a>(options: &'a str, config: &mut PlaybackConfig) -> Vec<&'a str> {
+    let mut rejected = Vec::new();
+
+    // NOTE: The shell passes the image path along as the first argument, so anything that isn't
+    // a flag is skipped rather than complained about
+    for arg in options
+        .split_whitespace()
+        .filter(|arg| arg.starts_with("--"))
+    {
+        let (flag, value) = match arg[2..].split_once('=') {
+            Some((flag, value)) => (flag, Some(value)),
+            None => (&arg[2..], None),
+        };
+
+        match (flag, value) {
+            ("fps", Some(value)) => match value.parse() {
+                Ok(fps) if fps != 0 => config.fps = fps,
+                _ => rejected.push(arg),
+            },
+            ("start", Some(value)) => match value.parse() {
+                Ok(start_frame) => config.start_frame = start_frame,
+                Err(_) => rejected.push(arg),
+            },
+            ("loop", None) => config.end = EndBehavior::Loop,
+            ("hold", None) => config.end = EndBehavior::HoldLastFrame,
+            ("reverse", None) => config.reverse = true,
+            ("step", None) => config.step_mode = true,
+            ("drop-frames", None) => config.drop_frames = true,
+            ("frame-index", None) => config.show_frame_index = true,
+            ("invert", None) => config.invert = true,
+            _ => rejected.push(arg),
+        }
+    }
+
+    rejected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_flag_combination() {
+        let mut config = PlaybackConfig::DEFAULT;
+        let rejected = apply(
+            "bad-apple.efi --fps=24 --loop --start=100 --reverse",
+            &mut config,
+        );
+
+        assert!(rejected.is_empty());
+        assert_eq!(config.fps, 24);
+        assert_eq!(config.start_frame, 100);
+        assert!(config.end == EndBehavior::Loop);
+        assert!(config.reverse);
+        assert!(!config.step_mode);
+    }
+
+    #[test]
+    fn apply_last_flag_wins() {
+        let mut config = PlaybackConfig::DEFAULT;
+        apply("--loop --hold --fps=5 --fps=30", &mut config);
+
+        assert!(config.end == EndBehavior::HoldLastFrame);
+        assert_eq!(config.fps, 30);
+    }
+
+    #[test]
+    fn apply_rejects_garbage() {
+        let mut config = PlaybackConfig::DEFAULT;
+        let rejected = apply(
+            "--fps=abc --mute --fps=0 --start=-1 --loop=1 --fps -- stray",
+            &mut config,
+        );
+
+        assert_eq!(
+            rejected,
+            [
+                "--fps=abc",
+                "--mute",
+                "--fps=0",
+                "--start=-1",
+                "--loop=1",
+                "--fps",
+                "--"
+            ]
+        );
+        assert_eq!(config.fps, PlaybackConfig::DEFAULT.fps);
+        assert_eq!(config.start_frame, PlaybackConfig::DEFAULT.start_frame);
+        assert!(config.end == PlaybackConfig::DEFAULT.end);
+    }
+
+    #[test]
+    fn apply_no_options() {
+        let mut config = PlaybackConfig::DEFAULT;
+
+        assert!(apply("", &mut config).is_empty());
+        assert!(apply("   bad-apple.efi  ", &mut config).is_empty());
+        assert_eq!(config.fps, PlaybackConfig::DEFAULT.fps);
+    }
+}
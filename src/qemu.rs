@@ -0,0 +1,61 @@
+//! Reports the result of a run to the host through QEMU's `isa-debug-exit`
+//! device, so that automated runs can tell a pass from a failure. QEMU needs to
+//! be started with `-device isa-debug-exit,iobase=0xf4,iosize=0x04`.
+
+use core::arch::asm;
+
+use uefi::Status;
+
+const ISA_DEBUG_EXIT_PORT: u16 = 0xf4;
+
+// NOTE: QEMU exits with `(code << 1) | 1`, so these come out as 33 and 35 on the host, which
+// can't be confused with QEMU's own exit codes
+const SUCCESS_CODE: u32 = 0x10;
+const FAILURE_CODE: u32 = 0x11;
+
+/// Exit code written to the device for `status`.
+const fn exit_code(status: Status) -> u32 {
+    match status {
+        Status::SUCCESS => SUCCESS_CODE,
+        _ => FAILURE_CODE,
+    }
+}
+
+/// Makes QEMU exit, reporting `status` to the host. Without the device this does
+/// nothing and returns.
+pub fn exit(status: Status) {
+    // SAFETY: The port is only decoded by QEMU's debug exit device, writes to it are ignored
+    // anywhere else
+    unsafe {
+        asm!(
+            "out dx, eax",
+            in("dx") ISA_DEBUG_EXIT_PORT,
+            in("eax") exit_code(status),
+            options(nomem, nostack, preserves_flags)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Status QEMU itself exits with after `code` is written to the device.
+    const fn host_exit_code(code: u32) -> u32 {
+        (code << 1) | 1
+    }
+
+    #[test]
+    fn exit_code_encoding() {
+        assert_eq!(exit_code(Status::SUCCESS), 0x10);
+        assert_eq!(exit_code(Status::UNSUPPORTED), 0x11);
+        assert_eq!(exit_code(Status::DEVICE_ERROR), 0x11);
+    }
+
+    #[test]
+    fn host_exit_codes() {
+        // NOTE: `make qemu-test` passes on exactly 33
+        assert_eq!(host_exit_code(exit_code(Status::SUCCESS)), 33);
+        assert_eq!(host_exit_code(exit_code(Status::UNSUPPORTED)), 35);
+    }
+}
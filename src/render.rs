@@ -0,0 +1,986 @@
+use alloc::{vec, vec::Vec};
+
+use uefi::proto::console::gop::BltPixel;
+
+use crate::config::PlaybackConfig;
+
+pub const WIDTH: usize = 300;
+pub const HEIGHT: usize = 240;
+
+// NOTE: This has to match `ASCII_CHARS` in `generate_ascii_art.py`, which picks each character
+// by dividing its pixel's luminance by 3.69
+const ASCII_RAMP: &[u8] =
+    b"$@B%8&WM#*oahkbdpqwmZO0QLCJUYXzcvunxrjft/\\|()1{}[]?-_+~<>i!lI;:,\"^`'. ";
+
+/// Luminance of each frame character, inverting how `generate_ascii_art.py` picked
+/// it. Anything that isn't part of the ramp counts as fully bright.
+const LUMINANCE: [u8; 256] = {
+    let mut luminance = [u8::MAX; 256];
+
+    let mut i = 0;
+    while i < ASCII_RAMP.len() {
+        luminance[ASCII_RAMP[i] as usize] = (i * 369 / 100) as u8;
+        i += 1;
+    }
+
+    luminance
+};
+
+/// Luminance of a frame character, from 0 to 255.
+pub const fn luminance(char: u8) -> u8 {
+    LUMINANCE[char as usize]
+}
+
+/// Orientation applied to each frame before it is written to the display, for
+/// panels that are mounted upside-down or in portrait.
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    None,
+    FlipH,
+    FlipV,
+    Rotate180,
+    Rotate90CW,
+    Rotate90CCW,
+}
+
+impl Transform {
+    /// Dimensions of a `width`x`height` frame once transformed.
+    pub const fn dims(self, width: usize, height: usize) -> (usize, usize) {
+        match self {
+            Transform::Rotate90CW | Transform::Rotate90CCW => (height, width),
+            _ => (width, height),
+        }
+    }
+
+    /// Transform which undoes this one.
+    pub const fn inverse(self) -> Self {
+        match self {
+            Transform::Rotate90CW => Transform::Rotate90CCW,
+            Transform::Rotate90CCW => Transform::Rotate90CW,
+            transform => transform,
+        }
+    }
+
+    /// Maps `(x, y)` within a `width`x`height` frame to its position in the
+    /// transformed frame.
+    pub const fn apply(self, x: usize, y: usize, width: usize, height: usize) -> (usize, usize) {
+        match self {
+            Transform::None => (x, y),
+            Transform::FlipH => (width - 1 - x, y),
+            Transform::FlipV => (x, height - 1 - y),
+            Transform::Rotate180 => (width - 1 - x, height - 1 - y),
+            Transform::Rotate90CW => (height - 1 - y, x),
+            Transform::Rotate90CCW => (y, width - 1 - x),
+        }
+    }
+}
+
+/// How the characters of a frame are turned into colors.
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RenderMode {
+    /// Split characters between the low and high colors at the threshold.
+    Threshold,
+    /// Shade every character by its luminance in the tint color, for a
+    /// monochrome look like a green phosphor screen.
+    Tint,
+    /// Shade every character by its luminance in warm sepia tones.
+    Sepia,
+    /// Shade every character in whichever color of the fixed palette is nearest
+    /// to its luminance, for a limited-palette look.
+    Palette,
+    /// Draw the outlines of shapes in the low color over the high color, found
+    /// with a Sobel operator over the luminance of each character.
+    Edges,
+}
+
+impl RenderMode {
+    /// Whether the mode only ever draws two colors, which leaves in-between shades
+    /// nothing to smooth.
+    pub const fn is_two_tone(self) -> bool {
+        matches!(self, RenderMode::Threshold | RenderMode::Edges)
+    }
+}
+
+/// How a frame is fitted onto the display.
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ScaleMode {
+    /// Draw the frame at its native size.
+    None,
+    /// Scale the frame up by the largest whole factor that fits on the display,
+    /// which keeps every pixel crisp.
+    Integer,
+    /// Draw the frame at its native size, cropping it evenly on any side that
+    /// doesn't fit on the display instead of needing a mode large enough for it.
+    CenterCrop,
+}
+
+impl ScaleMode {
+    /// Factor a `frame` sized frame is scaled by to fit a `display` sized display.
+    pub fn factor(self, frame: (usize, usize), display: (usize, usize)) -> usize {
+        match self {
+            ScaleMode::None | ScaleMode::CenterCrop => 1,
+            ScaleMode::Integer => (display.0 / frame.0).min(display.1 / frame.1).max(1),
+        }
+    }
+}
+
+/// Stretches a `frame` sized frame by the `pixel_aspect` width to height ratio
+/// of its pixels, so that it looks right on a display with square pixels.
+pub fn correct_aspect(
+    frame: (usize, usize),
+    (aspect_x, aspect_y): (usize, usize),
+) -> (usize, usize) {
+    // NOTE: Only ever stretching, never squashing, keeps every source pixel visible
+    match aspect_x >= aspect_y {
+        true => (frame.0 * aspect_x / aspect_y, frame.1),
+        false => (frame.0, frame.1 * aspect_y / aspect_x),
+    }
+}
+
+/// Picks the centered region of a `frame` sized frame that fits on a `display`
+/// sized display, as its top-left corner and size.
+pub fn center_crop(
+    frame: (usize, usize),
+    display: (usize, usize),
+) -> ((usize, usize), (usize, usize)) {
+    let (width, height) = (frame.0.min(display.0), frame.1.min(display.1));
+    (
+        ((frame.0 - width) / 2, (frame.1 - height) / 2),
+        (width, height),
+    )
+}
+
+/// Copies the `(x, y, width, height)` window of a `src_width` wide `src` into
+/// `dst`, which must be sized for the window.
+pub fn crop(
+    src: &[BltPixel],
+    src_width: usize,
+    (x, y, width, height): (usize, usize, usize, usize),
+    dst: &mut [BltPixel],
+) {
+    for (row, dst_row) in dst.chunks_mut(width).take(height).enumerate() {
+        dst_row.copy_from_slice(&src[(y + row) * src_width + x..][..width]);
+    }
+}
+
+/// Picks the display mode to play on out of the `resolutions` the firmware
+/// offers, for frames scaled to `needed`, returning its index. An `exact`
+/// resolution is used if it is offered and fits. Otherwise it is the smallest
+/// mode which fits a whole frame, where `crop` makes do with the largest one.
+pub fn pick_mode(
+    resolutions: &[(usize, usize)],
+    (needed_width, needed_height): (usize, usize),
+    crop: bool,
+    exact: Option<(usize, usize)>,
+) -> Option<usize> {
+    let fits = |&(width, height): &(usize, usize)| width >= needed_width && height >= needed_height;
+    let modes = || resolutions.iter().enumerate();
+
+    match exact {
+        // NOTE: A resolution that was asked for is known to be good, so nothing else is settled for
+        Some(exact) => modes()
+            .find(|(_, resolution)| **resolution == exact)
+            .filter(|(_, resolution)| crop || fits(resolution))
+            .map(|(i, _)| i),
+        // NOTE: Broken firmware can report 0x0 modes, and a frame can't be centered on anything
+        // smaller than itself, so the smallest mode which at least fits a whole frame is used.
+        // Cropping can make do with less, and then shows as much of a frame as it can
+        None => modes()
+            .filter(|(_, resolution)| fits(resolution))
+            .min_by_key(|(_, resolution)| **resolution)
+            .or_else(|| {
+                modes()
+                    .filter(|(_, &(width, height))| crop && width > 0 && height > 0)
+                    .max_by_key(|(_, resolution)| **resolution)
+            })
+            .map(|(i, _)| i),
+    }
+}
+
+/// Resizes a `src_dims` sized `src` into a `dst_dims` sized `dst` by picking the
+/// nearest source pixel for each destination pixel.
+pub fn resize_nearest(
+    src: &[BltPixel],
+    (src_width, src_height): (usize, usize),
+    dst: &mut [BltPixel],
+    (dst_width, dst_height): (usize, usize),
+) {
+    for (y, row) in dst.chunks_mut(dst_width).take(dst_height).enumerate() {
+        let src_row = &src[y * src_height / dst_height * src_width..][..src_width];
+        for (x, pixel) in row.iter_mut().enumerate() {
+            *pixel = src_row[x * src_width / dst_width];
+        }
+    }
+}
+
+/// Averages every color channel of `a` and `b` into `out`, giving a frame halfway
+/// between the two.
+pub fn blend_frames(a: &[BltPixel], b: &[BltPixel], out: &mut [BltPixel]) {
+    let average = |a: u8, b: u8| ((a as u16 + b as u16) / 2) as u8;
+
+    for ((a, b), out) in a.iter().zip(b).zip(out) {
+        *out = BltPixel::new(
+            average(a.red, b.red),
+            average(a.green, b.green),
+            average(a.blue, b.blue),
+        );
+    }
+}
+
+/// Upscales a `width`x`height` `src` into `dst` by replicating each pixel into a
+/// `scale`x`scale` block.
+pub fn upscale_integer(
+    src: &[BltPixel],
+    (width, height): (usize, usize),
+    scale: usize,
+    dst: &mut [BltPixel],
+) {
+    let dst_width = width * scale;
+
+    for (y, row) in src.chunks(width).take(height).enumerate() {
+        let start = y * scale * dst_width;
+        for (x, pixel) in row.iter().enumerate() {
+            dst[start + x * scale..][..scale].fill(*pixel);
+        }
+
+        // NOTE: The first scaled row is complete now, so the rest of the block is just copies of it
+        for i in 1..scale {
+            dst.copy_within(start..start + dst_width, start + i * dst_width);
+        }
+    }
+}
+
+/// Gamma lookup table which leaves every channel as it is.
+pub const IDENTITY_GAMMA: [u8; 256] = {
+    let mut gamma = [0; 256];
+
+    let mut i = 0;
+    while i < gamma.len() {
+        gamma[i] = i as u8;
+        i += 1;
+    }
+
+    gamma
+};
+
+/// Passes every channel of `color` through the `gamma` lookup table.
+pub const fn correct_gamma(color: BltPixel, gamma: &[u8; 256]) -> BltPixel {
+    BltPixel::new(
+        gamma[color.red as usize],
+        gamma[color.green as usize],
+        gamma[color.blue as usize],
+    )
+}
+
+/// Colors drawn for characters below and at or above the threshold, in that
+/// order, which swap places when the frame is inverted.
+pub const fn palette(config: &PlaybackConfig) -> (BltPixel, BltPixel) {
+    // NOTE: Frames only ever use a handful of colors, so correcting those keeps gamma off the
+    // per-pixel path entirely
+    let low = correct_gamma(config.low_color, config.gamma);
+    let high = correct_gamma(config.high_color, config.gamma);
+
+    match config.invert {
+        false => (low, high),
+        true => (high, low),
+    }
+}
+
+/// Color the display is cleared to, which also fills any part of a frame it
+/// doesn't cover.
+pub fn background(config: &PlaybackConfig) -> BltPixel {
+    let (_, high) = palette(config);
+    config
+        .background
+        .map(|background| correct_gamma(background, config.gamma))
+        .unwrap_or(high)
+}
+
+/// Luminance splitting `histogram` into the two classes with the largest
+/// variance between them, following Otsu's method, or `None` if there is only
+/// a single class to begin with.
+pub fn otsu_threshold(histogram: &[u32; 256]) -> Option<u8> {
+    let total = histogram.iter().map(|&count| count as u128).sum::<u128>();
+    let sum = histogram
+        .iter()
+        .enumerate()
+        .map(|(luminance, &count)| luminance as u128 * count as u128)
+        .sum::<u128>();
+
+    let (mut weight, mut weighted_sum) = (0, 0);
+    let mut best: Option<(u128, usize)> = None;
+    for (luminance, &count) in histogram.iter().enumerate() {
+        weight += count as u128;
+        weighted_sum += luminance as u128 * count as u128;
+
+        let other_weight = total - weight;
+        if weight == 0 || other_weight == 0 {
+            continue;
+        }
+
+        // NOTE: This is the between-class variance scaled by the total squared, which keeps it
+        // in integers without changing which split wins
+        let variance = (weighted_sum * other_weight)
+            .abs_diff((sum - weighted_sum) * weight)
+            .pow(2)
+            / (weight * other_weight);
+        if best.is_none_or(|(best_variance, _)| variance > best_variance) {
+            best = Some((variance, luminance));
+        }
+    }
+
+    // NOTE: The split is the brightest luminance of the dark class, so the threshold is the one
+    // after it. There is always something brighter left over, so this can't overflow
+    best.map(|(_, luminance)| (luminance + 1) as u8)
+}
+
+/// Luminance a character is shaded with, which is flipped when the frame is
+/// inverted.
+const fn shade(luminance: u8, config: &PlaybackConfig) -> u8 {
+    match config.invert {
+        false => luminance,
+        true => u8::MAX - luminance,
+    }
+}
+
+/// Scales every channel of `color` by a `luminance` from 0 to 255.
+pub fn tint(luminance: u8, color: BltPixel) -> BltPixel {
+    let scale = |channel: u8| (channel as u32 * luminance as u32 / 255) as u8;
+    BltPixel::new(scale(color.red), scale(color.green), scale(color.blue))
+}
+
+/// Tones `color` with the classic sepia matrix, in thousandths so that it stays
+/// in integers.
+pub fn sepia(color: BltPixel) -> BltPixel {
+    let (red, green, blue) = (color.red as u32, color.green as u32, color.blue as u32);
+    let mix = |r: u32, g: u32, b: u32| ((red * r + green * g + blue * b) / 1000).min(255) as u8;
+    BltPixel::new(mix(393, 769, 189), mix(349, 686, 168), mix(272, 534, 131))
+}
+
+/// Color in `palette` nearest to `color`, by squared RGB distance, or `None` if
+/// the palette is empty.
+pub fn nearest_color(color: BltPixel, palette: &[BltPixel]) -> Option<BltPixel> {
+    let distance = |other: &BltPixel| {
+        let channel = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        channel(color.red, other.red)
+            + channel(color.green, other.green)
+            + channel(color.blue, other.blue)
+    };
+
+    palette.iter().copied().min_by_key(distance)
+}
+
+/// Marks the edges of a `width`x`height` grayscale image in `out`, as 255 where
+/// the Sobel gradient magnitude reaches `threshold` and 0 everywhere else.
+/// Pixels past the border count as copies of the nearest one inside it.
+pub fn sobel_edges(gray: &[u8], (width, height): (usize, usize), out: &mut [u8], threshold: u8) {
+    let at = |x: usize, y: usize, dx: isize, dy: isize| {
+        let x = x.saturating_add_signed(dx).min(width - 1);
+        let y = y.saturating_add_signed(dy).min(height - 1);
+        gray[y * width + x] as i32
+    };
+
+    for y in 0..height {
+        for x in 0..width {
+            let gx = at(x, y, 1, -1) + 2 * at(x, y, 1, 0) + at(x, y, 1, 1)
+                - at(x, y, -1, -1)
+                - 2 * at(x, y, -1, 0)
+                - at(x, y, -1, 1);
+            let gy = at(x, y, -1, 1) + 2 * at(x, y, 0, 1) + at(x, y, 1, 1)
+                - at(x, y, -1, -1)
+                - 2 * at(x, y, 0, -1)
+                - at(x, y, 1, -1);
+
+            // NOTE: A step from black to white sums to 1020 along its axis, so this scales the
+            // sharpest edge to 255
+            let magnitude = ((gx.abs() + gy.abs()) / 4).min(255);
+            out[y * width + x] = match magnitude >= threshold as i32 {
+                true => u8::MAX,
+                false => 0,
+            };
+        }
+    }
+}
+
+/// Scratch space the edges mode works in, which is allocated once up front
+/// rather than for every frame. It is left empty for every other mode.
+pub struct EdgeBuffers {
+    luminance: Vec<u8>,
+    edges: Vec<u8>,
+}
+
+impl EdgeBuffers {
+    pub fn new(mode: RenderMode) -> Self {
+        let len = match mode {
+            RenderMode::Edges => WIDTH * HEIGHT,
+            _ => 0,
+        };
+
+        Self {
+            luminance: vec![0; len],
+            edges: vec![0; len],
+        }
+    }
+}
+
+/// Fills `grid` with the luminance of every character in `frame`, as a
+/// `WIDTH`x`HEIGHT` grid. Anything missing from short rows counts as fully bright.
+fn luminance_grid(frame: &str, grid: &mut [u8]) {
+    grid.fill(u8::MAX);
+    for (y, x_pixels) in frame
+        .split('\n')
+        .map(str::as_bytes)
+        .take(HEIGHT)
+        .enumerate()
+    {
+        for (x, x_pixel) in x_pixels.iter().take(WIDTH).enumerate() {
+            grid[y * WIDTH + x] = luminance(*x_pixel);
+        }
+    }
+}
+
+/// Renders the edges of an ASCII `frame` into `pixbuf`, which must be sized for
+/// the transformed frame dimensions.
+fn render_edges(
+    frame: &str,
+    config: &PlaybackConfig,
+    pixbuf: &mut [BltPixel],
+    buffers: &mut EdgeBuffers,
+) {
+    let (frame_width, _) = config.transform.dims(WIDTH, HEIGHT);
+    let (low, high) = palette(config);
+
+    // NOTE: The operator needs the neighbours of every pixel, so unlike the other modes this
+    // goes through a whole grid of luminance first
+    luminance_grid(frame, &mut buffers.luminance);
+    sobel_edges(
+        &buffers.luminance,
+        (WIDTH, HEIGHT),
+        &mut buffers.edges,
+        config.edge_threshold,
+    );
+
+    for (i, edge) in buffers.edges.iter().enumerate() {
+        let (x, y) = config.transform.apply(i % WIDTH, i / WIDTH, WIDTH, HEIGHT);
+        pixbuf[y * frame_width + x] = match *edge {
+            0 => high,
+            _ => low,
+        };
+    }
+}
+
+/// Threshold the characters of `frame` are split at, which is worked out from
+/// the frame itself when the threshold is automatic.
+fn frame_threshold(frame: &str, config: &PlaybackConfig) -> u8 {
+    if !config.auto_threshold {
+        return config.threshold;
+    }
+
+    let mut histogram = [0; 256];
+    for char in frame.bytes().filter(|&char| char != b'\n') {
+        histogram[luminance(char) as usize] += 1;
+    }
+
+    // NOTE: A frame of a single shade has nothing to split, so it keeps the fixed threshold
+    otsu_threshold(&histogram).unwrap_or(config.threshold)
+}
+
+/// Renders an ASCII `frame` into `pixbuf`, which must be sized for the
+/// transformed frame dimensions. `edge_buffers` must be made for the configured
+/// render mode.
+pub fn render_frame(
+    frame: &str,
+    config: &PlaybackConfig,
+    pixbuf: &mut [BltPixel],
+    edge_buffers: &mut EdgeBuffers,
+) {
+    if config.mode == RenderMode::Edges {
+        return render_edges(frame, config, pixbuf, edge_buffers);
+    }
+
+    let (frame_width, _) = config.transform.dims(WIDTH, HEIGHT);
+    let (low, high) = palette(config);
+    let threshold = frame_threshold(frame, config);
+
+    for (y, x_pixels) in frame.split('\n').map(str::as_bytes).enumerate() {
+        for (x, x_pixel) in x_pixels.iter().enumerate() {
+            // NOTE: Anything outside of the frame is dropped, since the transform
+            // only maps coordinates which lie within it
+            if x >= WIDTH || y >= HEIGHT {
+                continue;
+            }
+
+            // NOTE: `y * frame_width + x` is just normalizing the matrix indices into a 1D array index
+            let (x, y) = config.transform.apply(x, y, WIDTH, HEIGHT);
+            let real_pixel = &mut pixbuf[y * frame_width + x];
+
+            let luminance = luminance(*x_pixel);
+            *real_pixel = match config.mode {
+                RenderMode::Tint => {
+                    correct_gamma(tint(shade(luminance, config), config.tint), config.gamma)
+                }
+                RenderMode::Sepia => {
+                    let shade = shade(luminance, config);
+                    correct_gamma(sepia(BltPixel::new(shade, shade, shade)), config.gamma)
+                }
+                RenderMode::Palette => {
+                    let shade = shade(luminance, config);
+                    let gray = BltPixel::new(shade, shade, shade);
+
+                    // NOTE: Without any colors to pick from, the frame is just drawn in grayscale
+                    let color = nearest_color(gray, config.fixed_palette).unwrap_or(gray);
+                    correct_gamma(color, config.gamma)
+                }
+                // White by default, unless inverted
+                _ if luminance < threshold => low,
+                // Lighter shade of black by default, unless inverted
+                _ => high,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgb(pixel: &BltPixel) -> (u8, u8, u8) {
+        (pixel.red, pixel.green, pixel.blue)
+    }
+
+    /// A `WIDTH`x`HEIGHT` frame made up of nothing but `char`.
+    fn solid_frame(char: char) -> String {
+        let row = char.to_string().repeat(WIDTH);
+        vec![row; HEIGHT].join("\n")
+    }
+
+    #[test]
+    fn render_frame_solid() {
+        let config = PlaybackConfig::DEFAULT;
+        let (low, high) = palette(&config);
+
+        for (char, color) in [(' ', high), ('$', low)] {
+            let mut pixbuf = vec![BltPixel::new(1, 2, 3); WIDTH * HEIGHT];
+            render_frame(
+                &solid_frame(char),
+                &config,
+                &mut pixbuf,
+                &mut EdgeBuffers::new(config.mode),
+            );
+
+            assert!(pixbuf.iter().all(|pixel| rgb(pixel) == rgb(&color)));
+        }
+    }
+
+    #[test]
+    fn otsu_threshold_bimodal() {
+        let mut histogram = [0; 256];
+        histogram[40..60].fill(100);
+        histogram[190..210].fill(80);
+
+        let threshold = otsu_threshold(&histogram).unwrap();
+        assert!((60..=190).contains(&threshold));
+    }
+
+    #[test]
+    fn otsu_threshold_single_class() {
+        let mut histogram = [0; 256];
+        assert_eq!(otsu_threshold(&histogram), None);
+
+        histogram[128] = 1000;
+        assert_eq!(otsu_threshold(&histogram), None);
+    }
+
+    #[test]
+    fn otsu_threshold_extremes() {
+        let mut histogram = [0; 256];
+        histogram[0] = 1;
+        histogram[255] = 1;
+        assert_eq!(otsu_threshold(&histogram), Some(1));
+    }
+
+    #[test]
+    fn transform_apply() {
+        // NOTE: (1, 0) in a 4x3 frame, which rotates into a 3x4 one
+        let cases = [
+            (Transform::None, (1, 0)),
+            (Transform::FlipH, (2, 0)),
+            (Transform::FlipV, (1, 2)),
+            (Transform::Rotate180, (2, 2)),
+            (Transform::Rotate90CW, (2, 1)),
+            (Transform::Rotate90CCW, (0, 2)),
+        ];
+
+        for (transform, expected) in cases {
+            assert_eq!(transform.apply(1, 0, 4, 3), expected);
+        }
+
+        assert_eq!(Transform::Rotate90CW.dims(4, 3), (3, 4));
+        assert_eq!(Transform::FlipH.dims(4, 3), (4, 3));
+    }
+
+    #[test]
+    fn upscale_integer_doubles() {
+        let [a, b, c, d] =
+            [(1, 1, 1), (2, 2, 2), (3, 3, 3), (4, 4, 4)].map(|(r, g, b)| BltPixel::new(r, g, b));
+        let mut dst = vec![BltPixel::new(0, 0, 0); 16];
+        upscale_integer(&[a, b, c, d], (2, 2), 2, &mut dst);
+
+        let expected = [a, a, b, b, a, a, b, b, c, c, d, d, c, c, d, d];
+        assert_eq!(
+            dst.iter().map(rgb).collect::<Vec<_>>(),
+            expected.iter().map(rgb).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn correct_aspect_stretches() {
+        // NOTE: A 16:15 pixel is slightly wide, so only the width grows
+        assert_eq!(correct_aspect((300, 240), (16, 15)), (320, 240));
+        assert_eq!(correct_aspect((300, 240), (15, 16)), (300, 256));
+        assert_eq!(correct_aspect((300, 240), (1, 1)), (300, 240));
+    }
+
+    #[test]
+    fn center_crop_larger_frame() {
+        assert_eq!(center_crop((300, 240), (200, 100)), ((50, 70), (200, 100)));
+        assert_eq!(center_crop((300, 240), (640, 100)), ((0, 70), (300, 100)));
+    }
+
+    #[test]
+    fn center_crop_smaller_frame() {
+        assert_eq!(center_crop((300, 240), (640, 480)), ((0, 0), (300, 240)));
+    }
+
+    #[test]
+    fn crop_window() {
+        // NOTE: A 4x3 frame where each pixel's red channel is its index
+        let src = (0..12).map(|i| BltPixel::new(i, 0, 0)).collect::<Vec<_>>();
+        let mut dst = vec![BltPixel::new(0, 0, 0); 4];
+        crop(&src, 4, (1, 1, 2, 2), &mut dst);
+
+        assert_eq!(
+            dst.iter().map(|pixel| pixel.red).collect::<Vec<_>>(),
+            [5, 6, 9, 10]
+        );
+    }
+
+    #[test]
+    fn sobel_edges_step() {
+        // NOTE: Three black rows over three white ones, where both rows either side of the step
+        // see the whole jump
+        let mut gray = [0; 4 * 6];
+        gray[4 * 3..].fill(u8::MAX);
+
+        let mut out = [0; 4 * 6];
+        sobel_edges(&gray, (4, 6), &mut out, 128);
+
+        for (y, row) in out.chunks(4).enumerate() {
+            let expected = match y {
+                2 | 3 => u8::MAX,
+                _ => 0,
+            };
+            assert!(row.iter().all(|&edge| edge == expected), "row {y}");
+        }
+    }
+
+    #[test]
+    fn render_frame_edges_solid() {
+        let config = PlaybackConfig {
+            mode: RenderMode::Edges,
+            ..PlaybackConfig::DEFAULT
+        };
+        let (_, high) = palette(&config);
+
+        let mut pixbuf = vec![BltPixel::new(1, 2, 3); WIDTH * HEIGHT];
+        render_frame(
+            &solid_frame('$'),
+            &config,
+            &mut pixbuf,
+            &mut EdgeBuffers::new(config.mode),
+        );
+
+        assert!(pixbuf.iter().all(|pixel| rgb(pixel) == rgb(&high)));
+    }
+
+    #[test]
+    fn sepia_white_stays_warm() {
+        let (red, green, blue) = rgb(&sepia(BltPixel::new(255, 255, 255)));
+
+        assert_eq!((red, green), (255, 255));
+        assert!((200..255).contains(&blue));
+    }
+
+    #[test]
+    fn tint_mid_gray_green() {
+        let green = BltPixel::new(0, 255, 0);
+
+        assert_eq!(rgb(&tint(128, green)), (0, 128, 0));
+        assert_eq!(rgb(&tint(u8::MAX, green)), (0, 255, 0));
+        assert_eq!(rgb(&tint(0, green)), (0, 0, 0));
+    }
+
+    #[test]
+    fn nearest_color_picks_closest() {
+        let palette = [(0, 0, 0), (85, 255, 255), (255, 85, 255), (255, 255, 255)]
+            .map(|(r, g, b)| BltPixel::new(r, g, b));
+        let nearest = |(r, g, b)| {
+            nearest_color(BltPixel::new(r, g, b), &palette)
+                .as_ref()
+                .map(rgb)
+        };
+
+        assert_eq!(nearest((0, 0, 0)), Some((0, 0, 0)));
+        assert_eq!(nearest((0, 255, 255)), Some((85, 255, 255)));
+        assert_eq!(nearest((255, 0, 255)), Some((255, 85, 255)));
+        assert_eq!(nearest((240, 240, 240)), Some((255, 255, 255)));
+        assert_eq!(nearest((40, 40, 40)), Some((0, 0, 0)));
+    }
+
+    #[test]
+    fn nearest_color_empty_palette() {
+        assert!(nearest_color(BltPixel::new(1, 2, 3), &[]).is_none());
+    }
+
+    #[test]
+    fn blend_frames_averages_channels() {
+        let a = [BltPixel::new(0, 100, 255), BltPixel::new(10, 20, 30)];
+        let b = [BltPixel::new(255, 100, 0), BltPixel::new(11, 40, 90)];
+        let mut out = [BltPixel::new(0, 0, 0); 2];
+
+        blend_frames(&a, &b, &mut out);
+
+        assert_eq!(rgb(&out[0]), (127, 100, 127));
+        assert_eq!(rgb(&out[1]), (10, 30, 60));
+    }
+
+    #[test]
+    fn two_tone_modes() {
+        assert!(RenderMode::Threshold.is_two_tone());
+        assert!(RenderMode::Edges.is_two_tone());
+        assert!(!RenderMode::Tint.is_two_tone());
+        assert!(!RenderMode::Sepia.is_two_tone());
+        assert!(!RenderMode::Palette.is_two_tone());
+    }
+
+    #[test]
+    fn pick_mode_smallest_that_fits() {
+        let resolutions = [(1024, 768), (0, 0), (640, 480), (800, 600)];
+
+        assert_eq!(pick_mode(&resolutions, (640, 480), false, None), Some(2));
+        assert_eq!(pick_mode(&resolutions, (700, 500), false, None), Some(3));
+    }
+
+    #[test]
+    fn pick_mode_skips_zero_modes() {
+        // NOTE: A 0x0 mode fits a 0x0 frame, but nothing else
+        assert_eq!(
+            pick_mode(&[(0, 0), (640, 480)], (1, 1), false, None),
+            Some(1)
+        );
+        assert_eq!(pick_mode(&[(0, 0)], (320, 240), true, None), None);
+    }
+
+    #[test]
+    fn pick_mode_none_fits() {
+        let resolutions = [(640, 480), (0, 0), (800, 600)];
+
+        assert_eq!(pick_mode(&resolutions, (1920, 1080), false, None), None);
+    }
+
+    #[test]
+    fn pick_mode_crop_falls_back_to_largest() {
+        let resolutions = [(800, 600), (0, 0), (1024, 768), (640, 480)];
+
+        assert_eq!(pick_mode(&resolutions, (1920, 1080), true, None), Some(2));
+        // NOTE: Cropping still prefers a mode which fits the whole frame
+        assert_eq!(pick_mode(&resolutions, (700, 500), true, None), Some(0));
+    }
+
+    #[test]
+    fn pick_mode_exact_resolution() {
+        let resolutions = [(640, 480), (1024, 768), (800, 600)];
+
+        // NOTE: An exact resolution wins over the smallest mode that fits
+        assert_eq!(
+            pick_mode(&resolutions, (320, 240), false, Some((1024, 768))),
+            Some(1)
+        );
+        assert_eq!(
+            pick_mode(&resolutions, (320, 240), false, Some((1920, 1080))),
+            None
+        );
+    }
+
+    #[test]
+    fn pick_mode_exact_resolution_too_small() {
+        let resolutions = [(640, 480), (1024, 768)];
+
+        assert_eq!(
+            pick_mode(&resolutions, (800, 600), false, Some((640, 480))),
+            None
+        );
+        // NOTE: Cropping shows as much of the frame as fits instead
+        assert_eq!(
+            pick_mode(&resolutions, (800, 600), true, Some((640, 480))),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn render_frame_solid_inverted() {
+        let config = PlaybackConfig {
+            invert: true,
+            ..PlaybackConfig::DEFAULT
+        };
+        let (low, high) = (config.low_color, config.high_color);
+
+        // NOTE: Inverting swaps which of the two colors each character gets
+        for (char, color) in [(' ', low), ('$', high)] {
+            let mut pixbuf = vec![BltPixel::new(1, 2, 3); WIDTH * HEIGHT];
+            render_frame(
+                &solid_frame(char),
+                &config,
+                &mut pixbuf,
+                &mut EdgeBuffers::new(config.mode),
+            );
+
+            assert!(pixbuf.iter().all(|pixel| rgb(pixel) == rgb(&color)));
+        }
+    }
+
+    #[test]
+    fn background_follows_invert() {
+        let config = PlaybackConfig::DEFAULT;
+        let inverted = PlaybackConfig {
+            invert: true,
+            ..PlaybackConfig::DEFAULT
+        };
+
+        assert_eq!(rgb(&background(&config)), rgb(&config.high_color));
+        assert_eq!(rgb(&background(&inverted)), rgb(&config.low_color));
+    }
+
+    #[test]
+    fn render_frame_configured_colors_and_threshold() {
+        let (red, blue) = (BltPixel::new(255, 0, 0), BltPixel::new(0, 0, 255));
+        let config = PlaybackConfig {
+            low_color: red,
+            high_color: blue,
+            threshold: 128,
+            ..PlaybackConfig::DEFAULT
+        };
+
+        // NOTE: The characters either side of the threshold, rather than the extremes
+        let dim = *ASCII_RAMP
+            .iter()
+            .rfind(|&&char| luminance(char) < 128)
+            .unwrap();
+        let bright = *ASCII_RAMP
+            .iter()
+            .find(|&&char| luminance(char) >= 128)
+            .unwrap();
+        let frame = String::from_utf8(vec![dim, bright]).unwrap();
+
+        let mut pixbuf = vec![BltPixel::new(1, 2, 3); WIDTH * HEIGHT];
+        render_frame(
+            &frame,
+            &config,
+            &mut pixbuf,
+            &mut EdgeBuffers::new(config.mode),
+        );
+
+        assert_eq!(rgb(&pixbuf[0]), rgb(&red));
+        assert_eq!(rgb(&pixbuf[1]), rgb(&blue));
+        assert_eq!(rgb(&pixbuf[2]), (1, 2, 3));
+    }
+
+    #[test]
+    fn background_configured() {
+        let green = BltPixel::new(0, 255, 0);
+        let config = PlaybackConfig {
+            background: Some(green),
+            ..PlaybackConfig::DEFAULT
+        };
+
+        assert_eq!(rgb(&background(&config)), (0, 255, 0));
+    }
+
+    #[test]
+    fn background_fills_short_rows() {
+        let config = PlaybackConfig {
+            background: Some(BltPixel::new(0, 255, 0)),
+            ..PlaybackConfig::DEFAULT
+        };
+        let (low, _) = palette(&config);
+
+        // NOTE: Like the playback loop, the buffer is reset to the background before rendering,
+        // so whatever a short row doesn't reach keeps it
+        let mut pixbuf = vec![background(&config); WIDTH * HEIGHT];
+        render_frame(
+            "$$\n$",
+            &config,
+            &mut pixbuf,
+            &mut EdgeBuffers::new(config.mode),
+        );
+
+        for (i, pixel) in pixbuf.iter().enumerate() {
+            let expected = match i {
+                0 | 1 => rgb(&low),
+                _ if i == WIDTH => rgb(&low),
+                _ => (0, 255, 0),
+            };
+            assert_eq!(rgb(pixel), expected, "pixel {i}");
+        }
+    }
+
+    #[test]
+    fn resize_nearest_non_integer_stretch() {
+        let [a, b, c, d] =
+            [(1, 1, 1), (2, 2, 2), (3, 3, 3), (4, 4, 4)].map(|(r, g, b)| BltPixel::new(r, g, b));
+        let mut dst = vec![BltPixel::new(0, 0, 0); 6];
+        resize_nearest(&[a, b, c, d], (2, 2), &mut dst, (3, 2));
+
+        // NOTE: Stretching by 1.5 can't split a pixel, so the first of every row is doubled instead
+        let expected = [a, a, b, c, c, d];
+        assert_eq!(
+            dst.iter().map(rgb).collect::<Vec<_>>(),
+            expected.iter().map(rgb).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn resize_nearest_both_axes() {
+        let [a, b, c, d] =
+            [(1, 1, 1), (2, 2, 2), (3, 3, 3), (4, 4, 4)].map(|(r, g, b)| BltPixel::new(r, g, b));
+        let mut dst = vec![BltPixel::new(0, 0, 0); 9];
+        resize_nearest(&[a, b, c, d], (2, 2), &mut dst, (3, 3));
+
+        let expected = [a, a, b, a, a, b, c, c, d];
+        assert_eq!(
+            dst.iter().map(rgb).collect::<Vec<_>>(),
+            expected.iter().map(rgb).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn transform_inverse_round_trips() {
+        let transforms = [
+            Transform::None,
+            Transform::FlipH,
+            Transform::FlipV,
+            Transform::Rotate180,
+            Transform::Rotate90CW,
+            Transform::Rotate90CCW,
+        ];
+
+        for transform in transforms {
+            let (width, height) = transform.dims(4, 3);
+            let (x, y) = transform.apply(1, 0, 4, 3);
+            assert_eq!(transform.inverse().apply(x, y, width, height), (1, 0));
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! Bring-up checks for new hardware, which exercise the clock and display
+//! instead of playing the video.
+
+use uefi::{
+    println,
+    proto::console::gop::{BltOp, BltPixel, GraphicsOutput},
+    table::boot::BootServices,
+    Status,
+};
+
+use crate::{clock::Clock, config::PlaybackConfig};
+
+const BARS: [BltPixel; 8] = [
+    BltPixel::new(255, 255, 255),
+    BltPixel::new(255, 255, 0),
+    BltPixel::new(0, 255, 255),
+    BltPixel::new(0, 255, 0),
+    BltPixel::new(255, 0, 255),
+    BltPixel::new(255, 0, 0),
+    BltPixel::new(0, 0, 255),
+    BltPixel::new(0, 0, 0),
+];
+
+const GRID_SPACING: usize = 32;
+
+// NOTE: Firmware only steps the RTC once a second and the TSC is calibrated over a short stall,
+// so a few percent of error is expected even on healthy hardware
+const RTC_TOLERANCE_US: u64 = 50_000;
+
+// NOTE: The longest any RTC second should be waited on before it is considered stopped, with
+// plenty of margin over the tolerance for a slow `get_time`
+const RTC_TICK_TIMEOUT_US: u64 = 2_500_000;
+
+/// Ways measuring an RTC second can fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RtcError {
+    /// The firmware couldn't read the RTC.
+    Unreadable,
+    /// The RTC could be read, but its second didn't tick over in time.
+    Stopped,
+}
+
+/// Returns the RTC's current second, or `None` if the firmware can't read it.
+fn rtc_second() -> Option<u8> {
+    let system_table = uefi::helpers::system_table();
+    system_table
+        .runtime_services()
+        .get_time()
+        .ok()
+        .map(|time| time.second())
+}
+
+/// Measures how long the clock thinks a single RTC second takes, in microseconds.
+fn measure_rtc_second(clock: &Clock) -> Result<u64, RtcError> {
+    // NOTE: Waiting for the second to tick over first lines the measurement up with its start
+    let wait_for_tick = || {
+        let second = rtc_second().ok_or(RtcError::Unreadable)?;
+        let wait_start = clock.now_us();
+        while rtc_second().ok_or(RtcError::Unreadable)? == second {
+            if clock.now_us() - wait_start > RTC_TICK_TIMEOUT_US {
+                return Err(RtcError::Stopped);
+            }
+        }
+        Ok(clock.now_us())
+    };
+
+    let start = wait_for_tick()?;
+    let end = wait_for_tick()?;
+    Ok(end - start)
+}
+
+/// Fills a `dims` sized area at `dest` with `color`.
+fn fill(gop: &mut GraphicsOutput, color: BltPixel, dest: (usize, usize), dims: (usize, usize)) {
+    gop.blt(BltOp::VideoFill { color, dest, dims })
+        .expect("failed to fill display");
+}
+
+/// Draws color bars over the top two thirds of the display, and a grid below
+/// them to check for skew or scaling.
+fn draw_test_pattern(gop: &mut GraphicsOutput) {
+    let (width, height) = gop.current_mode_info().resolution();
+    let bars_height = height * 2 / 3;
+
+    for (i, color) in BARS.iter().enumerate() {
+        let start = i * width / BARS.len();
+        let end = (i + 1) * width / BARS.len();
+        fill(gop, *color, (start, 0), (end - start, bars_height));
+    }
+
+    let (white, black) = (BARS[0], BARS[7]);
+    fill(gop, black, (0, bars_height), (width, height - bars_height));
+    for x in (0..width).step_by(GRID_SPACING) {
+        fill(gop, white, (x, bars_height), (1, height - bars_height));
+    }
+    for y in (bars_height..height).step_by(GRID_SPACING) {
+        fill(gop, white, (0, y), (width, 1));
+    }
+}
+
+pub fn run(
+    boot_services: &BootServices,
+    config: &PlaybackConfig,
+    gop: &mut GraphicsOutput,
+) -> Status {
+    draw_test_pattern(gop);
+
+    let clock = Clock::calibrate(boot_services, config.calibration_ms as u64 * 1000);
+    println!(
+        "selftest: clock runs at {} TSC ticks per us",
+        clock.ticks_per_us()
+    );
+
+    let elapsed_us = measure_rtc_second(&clock);
+    match elapsed_us {
+        Ok(elapsed_us) => println!(
+            "selftest: one RTC second took {elapsed_us} us, off by {} us",
+            elapsed_us.abs_diff(1_000_000)
+        ),
+        Err(RtcError::Unreadable) => println!("selftest: failed to read the RTC"),
+        Err(RtcError::Stopped) => println!(
+            "selftest: the RTC didn't tick within {RTC_TICK_TIMEOUT_US} us, it may have stopped"
+        ),
+    }
+
+    let status = clock_status(elapsed_us);
+    if status.is_error() {
+        println!("selftest: clock check failed, tolerance is {RTC_TOLERANCE_US} us");
+    }
+
+    status
+}
+
+/// Result of the clock check, given how long one RTC second measured as.
+fn clock_status(elapsed_us: Result<u64, RtcError>) -> Status {
+    match elapsed_us {
+        Err(RtcError::Unreadable) => Status::UNSUPPORTED,
+        Err(RtcError::Stopped) => Status::DEVICE_ERROR,
+        Ok(elapsed_us) if elapsed_us.abs_diff(1_000_000) > RTC_TOLERANCE_US => Status::DEVICE_ERROR,
+        Ok(_) => Status::SUCCESS,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_status_tolerance() {
+        assert_eq!(clock_status(Ok(1_000_000)), Status::SUCCESS);
+        assert_eq!(
+            clock_status(Ok(1_000_000 - RTC_TOLERANCE_US)),
+            Status::SUCCESS
+        );
+        assert_eq!(
+            clock_status(Ok(1_000_000 + RTC_TOLERANCE_US)),
+            Status::SUCCESS
+        );
+        assert_eq!(
+            clock_status(Ok(1_000_001 + RTC_TOLERANCE_US)),
+            Status::DEVICE_ERROR
+        );
+        assert_eq!(clock_status(Ok(500_000)), Status::DEVICE_ERROR);
+    }
+
+    #[test]
+    fn clock_status_unreadable_rtc() {
+        assert_eq!(clock_status(Err(RtcError::Unreadable)), Status::UNSUPPORTED);
+    }
+
+    #[test]
+    fn clock_status_stopped_rtc() {
+        assert_eq!(clock_status(Err(RtcError::Stopped)), Status::DEVICE_ERROR);
+    }
+}
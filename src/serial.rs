@@ -0,0 +1,105 @@
+use alloc::vec::Vec;
+
+use uefi::{
+    proto::console::serial::{ControlBits, Serial},
+    table::boot::{BootServices, OpenProtocolAttributes, OpenProtocolParams, ScopedProtocol, Tpl},
+};
+
+/// Byte sent through the loopback by `probe`.
+const PROBE_BYTE: u8 = 0x5a;
+
+/// Opens the firmware's serial port without taking it away from the console,
+/// which keeps mirroring its output there too. Returns `None` if there is no
+/// serial port, or if it doesn't get a byte through its own loopback.
+///
+/// Probing costs up to a read timeout, so the port should be opened once and
+/// shared by everything written over it.
+pub fn open(boot_services: &BootServices) -> Option<ScopedProtocol<'_, Serial>> {
+    let handle = boot_services.get_handle_for_protocol::<Serial>().ok()?;
+
+    // SAFETY: The console keeps using the port as well, which is fine as long as it is left the way
+    // the console expects. Its attributes are never changed, and `probe` only flips the loopback
+    // bit with the console's timer held off, restoring the control bits before returning. Nothing
+    // uninstalls the protocol while boot services are active
+    let mut serial = unsafe {
+        boot_services.open_protocol::<Serial>(
+            OpenProtocolParams {
+                handle,
+                agent: boot_services.image_handle(),
+                controller: None,
+            },
+            OpenProtocolAttributes::GetProtocol,
+        )
+    }
+    .ok()?;
+
+    match probe(boot_services, &mut serial) {
+        Some(false) => None,
+        _ => Some(serial),
+    }
+}
+
+/// Checks that `serial` actually moves bytes, by sending one through the UART's
+/// hardware loopback and reading it back. Returns `None` if the firmware can't
+/// loop the port back, like for serial over USB, so nothing is known either way.
+fn probe(boot_services: &BootServices, serial: &mut Serial) -> Option<bool> {
+    let bits = serial.get_control_bits().ok()? & ControlBits::SETTABLE;
+    serial
+        .set_control_bits(bits | ControlBits::HARDWARE_LOOPBACK_ENABLE)
+        .ok()?;
+
+    // NOTE: The console polls the same port for input from a timer, which would otherwise
+    // swallow the byte before it could be read back. Anything already waiting in the receive
+    // FIFO comes out first, so up to that many bytes are read past. A port which accepts the
+    // loopback bit but never echoes costs a single read timeout, a second by default
+    let (written, read_back) = {
+        // SAFETY: Nothing but the serial port is touched until the guard drops, and reads
+        // only stall for up to the port's timeout
+        let _tpl = unsafe { boot_services.raise_tpl(Tpl::NOTIFY) };
+
+        let written = serial.write(&[PROBE_BYTE]).is_ok();
+        let fifo_depth = serial.io_mode().receive_fifo_depth as usize;
+        let mut read_back = Vec::new();
+        while written && read_back.len() <= fifo_depth && read_back.last() != Some(&PROBE_BYTE) {
+            let mut byte = [0];
+            match serial.read(&mut byte) {
+                Ok(()) => read_back.push(byte[0]),
+                Err(_) => break,
+            }
+        }
+
+        (written, read_back)
+    };
+
+    let restored = serial.set_control_bits(bits).is_ok();
+
+    Some(loopback_passed(written, &read_back, restored))
+}
+
+/// Whether a loopback probe passed, given whether `PROBE_BYTE` could be sent,
+/// the bytes read back after it and whether the loopback was turned back off.
+fn loopback_passed(written: bool, read_back: &[u8], restored: bool) -> bool {
+    // NOTE: Output is swallowed for as long as the loopback is on, so failing to turn it back off
+    // makes the port as good as dead
+    written && restored && read_back.contains(&PROBE_BYTE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loopback_passed_on_echo() {
+        assert!(loopback_passed(true, &[PROBE_BYTE], true));
+        // NOTE: Stale input comes out ahead of the echo
+        assert!(loopback_passed(true, b"ab\x5a", true));
+    }
+
+    #[test]
+    fn loopback_failed() {
+        assert!(!loopback_passed(true, &[], true));
+        assert!(!loopback_passed(true, b"abc", true));
+        assert!(!loopback_passed(false, &[], true));
+        assert!(!loopback_passed(true, &[PROBE_BYTE], false));
+    }
+}
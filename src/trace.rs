@@ -0,0 +1,66 @@
+use core::fmt;
+
+/// Marks the lines of the trace, so they can be picked out of everything else
+/// the firmware console mirrors to the same serial port.
+pub const PREFIX: &str = "TRACE ";
+
+/// Column names of the per-frame trace, written once before the first frame.
+pub const HEADER: &str = "frame_index,intended_us,actual_us,render_us,draw_us";
+
+/// Timing of a single frame, written over serial as a CSV line so that drift can
+/// be plotted on the host. All timestamps are on the playback clock.
+pub struct FrameTrace {
+    pub index: usize,
+    /// When the frame was due to be presented.
+    pub intended_us: u64,
+    /// When the frame actually started being presented.
+    pub actual_us: u64,
+    /// How long rendering the frame into its buffer took.
+    pub render_us: u64,
+    /// How long scaling and transferring the frame to the display took.
+    pub draw_us: u64,
+}
+
+impl fmt::Display for FrameTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{},{},{}",
+            self.index, self.intended_us, self.actual_us, self.render_us, self.draw_us
+        )
+    }
+}
+
+/// Writes `line` to `out` as a line of the trace.
+pub fn write_line(out: &mut impl fmt::Write, line: impl fmt::Display) -> fmt::Result {
+    // NOTE: The console may have left the cursor mid-line, like after the frame index overlay, so
+    // the line is started fresh and finished off to keep both apart
+    write!(out, "\r\n{PREFIX}{line}\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_line_is_prefixed_and_separated() {
+        let mut out = String::from("frame    12");
+        let frame_trace = FrameTrace {
+            index: 12,
+            intended_us: 400_000,
+            actual_us: 401_250,
+            render_us: 900,
+            draw_us: 2_100,
+        };
+
+        write_line(&mut out, HEADER).unwrap();
+        write_line(&mut out, &frame_trace).unwrap();
+
+        let trace_lines: Vec<_> = out
+            .lines()
+            .filter_map(|line| line.trim_end().strip_prefix(PREFIX))
+            .collect();
+        assert_eq!(trace_lines, [HEADER, "12,400000,401250,900,2100"]);
+        assert!(out.starts_with("frame    12\r\n"));
+    }
+}